@@ -2,3 +2,29 @@ use anchor_lang::prelude::*;
 
 #[constant]
 pub const SEED: &str = "anchor";
+
+// Maximum number of resting orders a single user can have on one orderbook
+pub const MAX_OPEN_ORDERS: usize = 64;
+
+// Fee rates are in basis points of the quote amount of a fill
+pub const FEE_BPS_DENOMINATOR: u64 = 10_000;
+
+// Highest maker or taker fee an orderbook can charge, 10%
+#[constant]
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+// Number of volume-based fee tiers an orderbook can define
+pub const MAX_FEE_TIERS: usize = 8;
+
+// Number of days over which a user's traded volume counts towards their fee tier. The
+// window rolls forward a day at a time, so each day's volume drops out 30 days later.
+pub const FEE_VOLUME_DAYS: usize = 30;
+
+// Number of maker events an event queue holds before matching has to wait for consume_events
+pub const EVENT_QUEUE_CAPACITY: usize = 64;
+
+// Number of recent trades a trade history keeps
+pub const TRADE_HISTORY_CAPACITY: usize = 64;
+
+// Number of candles kept for each interval, e.g. the last 48 minutes of 1 minute candles
+pub const CANDLE_COUNT: usize = 48;
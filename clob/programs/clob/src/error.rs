@@ -36,5 +36,68 @@ pub enum ErrorCode {
     InsufficientBalance,
 
     #[msg("Operation not authorized")]
-    Unauthorized
+    Unauthorized,
+
+    #[msg("Market buy orders require a quote budget greater than zero")]
+    InvalidQuoteBudget,
+
+    #[msg("Invalid time in force")]
+    InvalidTimeInForce,
+
+    #[msg("Fill-or-kill order cannot be completely filled")]
+    FillOrKillNotFilled,
+
+    #[msg("Post-only order would cross the book")]
+    WouldCrossBook,
+
+    #[msg("Invalid self-trade prevention mode")]
+    InvalidSelfTradePrevention,
+
+    #[msg("Order capacity must be non-zero and fit the resting orders")]
+    InvalidOrderCapacity,
+
+    #[msg("Too many open orders for this user")]
+    TooManyOpenOrders,
+
+    #[msg("Open orders account is neither the taker's nor the referrer's")]
+    OpenOrdersNotFound,
+
+    #[msg("Open orders account belongs to a different orderbook")]
+    InvalidOpenOrders,
+
+    #[msg("Vault token amounts do not cover user balances")]
+    VaultBalanceMismatch,
+
+    #[msg("Fee rate exceeds the maximum allowed")]
+    InvalidFeeRate,
+
+    #[msg("Fee tiers must be sorted by volume and within the tier limit")]
+    InvalidFeeTiers,
+
+    #[msg("Maker rebate exceeds the lowest taker fee")]
+    RebateExceedsTakerFee,
+
+    #[msg("Referrer must be another user's open orders account on this orderbook")]
+    InvalidReferrer,
+
+    #[msg("Tick size, base lot size and min order size must be non-zero, and min order size a multiple of the lot size")]
+    InvalidMarketParams,
+
+    #[msg("Price must be a multiple of the tick size")]
+    InvalidTickSize,
+
+    #[msg("Amount must be a whole number of base lots")]
+    InvalidLotSize,
+
+    #[msg("Order amount is below the minimum order size")]
+    OrderTooSmall,
+
+    #[msg("Orderbook accounting invariant does not hold")]
+    InvariantViolation,
+
+    #[msg("Event queue is full, run consume_events before matching more orders")]
+    EventQueueFull,
+
+    #[msg("Account data is not a trade history")]
+    InvalidTradeHistory
 }
@@ -0,0 +1,86 @@
+use anchor_lang::prelude::*;
+
+use crate::state::Side;
+
+// Events are emitted to the program logs for indexers. Amounts are in base or quote
+// atoms and prices in quote atoms per base lot, as everywhere else in the program.
+
+// A limit or market order entered the book, before it is matched. For a market order
+// the price is its worst price.
+#[event]
+pub struct OrderPlaced {
+    pub order_book: Pubkey,
+    pub order_id: u64,
+    pub owner: Pubkey,
+    pub side: Side,
+    pub price: u64,
+    pub amount: u64,
+    pub market: bool,
+    pub timestamp: i64,
+}
+
+// One fill between a taker order and a resting order, at the resting order's price.
+// A maker rebate is paid out of the taker fee.
+#[event]
+pub struct OrderFilled {
+    pub order_book: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub maker_order_id: u64,
+    pub taker_order_id: u64,
+    pub taker_side: Side,
+    pub price: u64,
+    pub amount: u64,
+    pub quote_amount: u64,
+    pub taker_fee: u64,
+    pub maker_fee: u64,
+    pub maker_rebate: u64,
+    pub timestamp: i64,
+}
+
+// Part or all of an order was cancelled, by its owner, by self-trade prevention, or
+// because its time in force didn't let the unfilled part rest
+#[event]
+pub struct OrderCancelled {
+    pub order_book: Pubkey,
+    pub order_id: u64,
+    pub owner: Pubkey,
+    pub side: Side,
+    pub cancelled_amount: u64,
+    pub remaining_amount: u64,
+}
+
+#[event]
+pub struct Deposit {
+    pub order_book: Pubkey,
+    pub owner: Pubkey,
+    pub base_amount: u64,
+    pub quote_amount: u64,
+}
+
+#[event]
+pub struct Withdraw {
+    pub order_book: Pubkey,
+    pub owner: Pubkey,
+    pub base_amount: u64,
+    pub quote_amount: u64,
+}
+
+// open_orders is None when the orderbook and its order slab, event queue, trade history
+// and candles were delegated. Anyone can delegate, so signer is whoever sent the
+// transaction rather than the orderbook's authority.
+#[event]
+pub struct Delegated {
+    pub order_book: Pubkey,
+    pub open_orders: Option<Pubkey>,
+    pub signer: Pubkey,
+}
+
+// open_orders is None when the orderbook and its order slab, event queue, trade history
+// and candles were undelegated. signer is whoever sent the transaction, as for Delegated.
+#[event]
+pub struct Undelegated {
+    pub order_book: Pubkey,
+    pub open_orders: Option<Pubkey>,
+    pub signer: Pubkey,
+}
@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::state::{Book, Orderbook, OrderSlab, Side};
+
+#[derive(Accounts)]
+pub struct Audit<'info> {
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+
+    #[account(
+        seeds = [b"order_slab", order_book.key().as_ref()],
+        bump = order_slab.load()?.bump
+    )]
+    pub order_slab: AccountLoader<'info, OrderSlab>,
+
+    #[account(
+        associated_token::mint = base_token_mint,
+        associated_token::authority = order_book,
+        associated_token::token_program = token_program,
+    )]
+    pub base_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        associated_token::mint = quote_token_mint,
+        associated_token::authority = order_book,
+        associated_token::token_program = token_program,
+    )]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// Read-only check that anyone can run, e.g. from monitoring, to catch accounting bugs
+// as soon as they happen. Fails with InvariantViolation or VaultBalanceMismatch.
+pub fn handle_audit(ctx: Context<Audit>) -> Result<()> {
+    let order_book = &ctx.accounts.order_book;
+    let order_slab = ctx.accounts.order_slab.to_account_info();
+    let mut order_slab_data = order_slab.try_borrow_mut_data()?;
+    let book = Book::load(&mut order_slab_data)?;
+
+    let base_vault_amount = ctx.accounts.base_vault.amount;
+    let quote_vault_amount = ctx.accounts.quote_vault.amount;
+    order_book.check_invariants(&book, base_vault_amount, quote_vault_amount)?;
+
+    let (base_amount, quote_amount) = order_book.totals.total()?;
+    msg!("Audit passed, bids: {}, asks: {}, surplus base: {}, quote: {}",
+        book.len(Side::Buy), book.len(Side::Sell),
+        base_vault_amount - base_amount,
+        quote_vault_amount - quote_amount - order_book.accrued_fees);
+    Ok(())
+}
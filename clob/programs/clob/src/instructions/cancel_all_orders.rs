@@ -0,0 +1,86 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::state::{Book, OpenOrders, Orderbook, OrderSlab, Side};
+use crate::error::ErrorCode;
+use crate::events::OrderCancelled;
+
+#[derive(Accounts)]
+pub struct CancelAllOrders<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut,
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+
+    #[account(mut,
+        seeds = [b"order_slab", order_book.key().as_ref()],
+        bump = order_slab.load()?.bump
+    )]
+    pub order_slab: AccountLoader<'info, OrderSlab>,
+
+    #[account(mut,
+        seeds = [b"open_orders", order_book.key().as_ref(), user.key().as_ref()],
+        bump = open_orders.bump
+    )]
+    pub open_orders: Account<'info, OpenOrders>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct CancelAllResult {
+    pub cancelled_count: u64,
+    pub base_refunded: u64,
+    pub quote_refunded: u64,
+}
+
+pub fn handle_cancel_all_orders(context: Context<CancelAllOrders>, side: Option<Side>) -> Result<CancelAllResult> {
+    let order_book = &mut context.accounts.order_book;
+    let open_orders = &mut context.accounts.open_orders;
+    let user = &context.accounts.user;
+    let order_slab = context.accounts.order_slab.to_account_info();
+    let mut order_slab_data = order_slab.try_borrow_mut_data()?;
+    let mut book = Book::load(&mut order_slab_data)?;
+
+    let cancelled = book.remove_orders_by_owner(&user.key(), side);
+
+    let mut result = CancelAllResult {
+        cancelled_count: cancelled.len() as u64,
+        base_refunded: 0,
+        quote_refunded: 0,
+    };
+    for order in &cancelled {
+        open_orders.remove_order_id(order.id);
+        let (base_amount, quote_amount) = order.locked_amounts(order_book.lot_size())?;
+        result.base_refunded = result.base_refunded
+            .checked_add(base_amount)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        result.quote_refunded = result.quote_refunded
+            .checked_add(quote_amount)
+            .ok_or(ErrorCode::CalculationFailure)?;
+
+        emit!(OrderCancelled {
+            order_book: order_book.key(),
+            order_id: order.id,
+            owner: order.owner,
+            side: order.side,
+            cancelled_amount: order.remaining_amount,
+            remaining_amount: 0,
+        });
+    }
+
+    // Release everything the cancelled orders were holding in a single balance update
+    open_orders.unlock(order_book, result.base_refunded, result.quote_refunded)?;
+
+    msg!("Cancelled {} orders, refunded base: {}, quote: {}",
+        result.cancelled_count, result.base_refunded, result.quote_refunded);
+
+    Ok(result)
+}
@@ -0,0 +1,82 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::state::{Book, OpenOrders, Order, Orderbook, OrderSlab};
+use crate::error::ErrorCode;
+use crate::events::OrderCancelled;
+
+#[derive(Accounts)]
+pub struct CancelOrder<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut,
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+
+    #[account(mut,
+        seeds = [b"order_slab", order_book.key().as_ref()],
+        bump = order_slab.load()?.bump
+    )]
+    pub order_slab: AccountLoader<'info, OrderSlab>,
+
+    #[account(mut,
+        seeds = [b"open_orders", order_book.key().as_ref(), user.key().as_ref()],
+        bump = open_orders.bump
+    )]
+    pub open_orders: Account<'info, OpenOrders>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_cancel_order(context: Context<CancelOrder>, order_id: u64) -> Result<()> {
+    let order_book = &mut context.accounts.order_book;
+    let open_orders = &mut context.accounts.open_orders;
+    let order_slab = context.accounts.order_slab.to_account_info();
+    let mut order_slab_data = order_slab.try_borrow_mut_data()?;
+    let mut book = Book::load(&mut order_slab_data)?;
+
+    cancel_resting_order(&mut book, order_book, open_orders, order_id)?;
+    Ok(())
+}
+
+// Take one of the user's orders off the book and release the funds it was holding back
+// to their free balance. Returns the cancelled order.
+pub(crate) fn cancel_resting_order(
+    book: &mut Book,
+    order_book: &mut Orderbook,
+    open_orders: &mut OpenOrders,
+    order_id: u64,
+) -> Result<Order> {
+    let (side, index) = match book.find_order(order_id) {
+        Some(location) => location,
+        None => {
+            msg!("Order not found with ID: {}", order_id);
+            return err!(ErrorCode::OrderNotFound);
+        }
+    };
+    require!(book.order_at(side, index).owner == open_orders.owner, ErrorCode::NotOrderOwner);
+    let order = book.remove_order(side, index);
+
+    // Release the funds the order was holding back to the user's balance
+    let (base_amount, quote_amount) = order.locked_amounts(order_book.lot_size())?;
+    open_orders.unlock(order_book, base_amount, quote_amount)?;
+    open_orders.remove_order_id(order_id);
+
+    emit!(OrderCancelled {
+        order_book: open_orders.order_book,
+        order_id,
+        owner: order.owner,
+        side,
+        cancelled_amount: order.remaining_amount,
+        remaining_amount: 0,
+    });
+
+    msg!("Cancelled order {}, refunded base: {}, quote: {}", order_id, base_amount, quote_amount);
+    Ok(order)
+}
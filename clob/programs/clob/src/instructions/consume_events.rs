@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::state::{EventQueue, OpenOrders, Orderbook};
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+pub struct ConsumeEvents<'info> {
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+
+    #[account(mut,
+        seeds = [b"event_queue", order_book.key().as_ref()],
+        bump = event_queue.load()?.bump
+    )]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+}
+
+// Anyone can crank the queue. Open orders accounts of the makers of the queued events are
+// passed as remaining accounts; consuming stops early at an event whose maker's account
+// is missing.
+pub fn handle_consume_events<'info>(
+    context: Context<'_, '_, 'info, 'info, ConsumeEvents<'info>>,
+    limit: u16,
+) -> Result<()> {
+    let order_book_key = context.accounts.order_book.key();
+    let mut makers: Vec<Account<'info, OpenOrders>> = Vec::new();
+    for account_info in context.remaining_accounts {
+        let maker = Account::<OpenOrders>::try_from(account_info)?;
+        require_keys_eq!(maker.order_book, order_book_key, ErrorCode::InvalidOpenOrders);
+
+        // Each owner has a single account per orderbook, so skip repeats rather than
+        // keeping two copies that would overwrite each other on exit
+        if makers.iter().any(|known| known.owner == maker.owner) {
+            continue;
+        }
+        makers.push(maker);
+    }
+
+    let mut event_queue = context.accounts.event_queue.load_mut()?;
+    let mut maker_accounts: Vec<&mut OpenOrders> = makers.iter_mut().map(|maker| &mut **maker).collect();
+    let consumed = event_queue.consume(&mut maker_accounts, limit as usize)?;
+    for maker in &makers {
+        maker.exit(&crate::ID)?;
+    }
+
+    msg!("Consumed {} events, {} left in the queue", consumed, event_queue.len());
+    Ok(())
+}
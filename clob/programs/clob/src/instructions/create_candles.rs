@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::state::{Candles, Orderbook};
+
+// Adds the candles account to an orderbook created before matching kept candles.
+// Anyone can pay for it.
+#[derive(Accounts)]
+pub struct CreateCandles<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+
+    #[account(init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<Candles>(),
+        seeds = [b"candles", order_book.key().as_ref()],
+        bump)]
+    pub candles: AccountLoader<'info, Candles>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_create_candles(context: Context<CreateCandles>) -> Result<()> {
+    context.accounts.candles.load_init()?
+        .initialize(context.accounts.order_book.key(), context.bumps.candles);
+    msg!("Created candles {} for orderbook {}", context.accounts.candles.key(), context.accounts.order_book.key());
+    Ok(())
+}
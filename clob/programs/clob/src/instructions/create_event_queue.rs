@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::state::{EventQueue, Orderbook};
+
+// Adds the event queue to an orderbook created before matching settled makers through it.
+// Anyone can pay for it.
+#[derive(Accounts)]
+pub struct CreateEventQueue<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+
+    #[account(init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<EventQueue>(),
+        seeds = [b"event_queue", order_book.key().as_ref()],
+        bump)]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_create_event_queue(context: Context<CreateEventQueue>) -> Result<()> {
+    context.accounts.event_queue.load_init()?
+        .initialize(context.accounts.order_book.key(), context.bumps.event_queue);
+    msg!("Created event queue {} for orderbook {}", context.accounts.event_queue.key(), context.accounts.order_book.key());
+    Ok(())
+}
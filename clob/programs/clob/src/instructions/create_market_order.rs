@@ -0,0 +1,201 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::token_interface::Mint;
+
+use crate::state::{Book, Candles, EventQueue, MarketHistory, OpenOrders, Orderbook, OrderSlab, TradeHistory, Traders, TakerFunds, Order, SelfTradePrevention, Side};
+use crate::error::ErrorCode;
+use crate::events::{OrderCancelled, OrderPlaced};
+
+#[derive(Accounts)]
+pub struct CreateMarketOrder<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut,
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+
+    #[account(mut,
+        seeds = [b"order_slab", order_book.key().as_ref()],
+        bump = order_slab.load()?.bump
+    )]
+    pub order_slab: AccountLoader<'info, OrderSlab>,
+
+    #[account(mut,
+        seeds = [b"event_queue", order_book.key().as_ref()],
+        bump = event_queue.load()?.bump
+    )]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+
+    #[account(mut,
+        seeds = [b"trade_history", order_book.key().as_ref()],
+        bump = trade_history.load()?.bump
+    )]
+    pub trade_history: AccountLoader<'info, TradeHistory>,
+
+    #[account(mut,
+        seeds = [b"candles", order_book.key().as_ref()],
+        bump = candles.load()?.bump
+    )]
+    pub candles: AccountLoader<'info, Candles>,
+
+    #[account(mut,
+        seeds = [b"open_orders", order_book.key().as_ref(), user.key().as_ref()],
+        bump = open_orders.bump
+    )]
+    pub open_orders: Account<'info, OpenOrders>,
+
+    // Open orders account of whoever referred the user, credited with a share of the taker fee
+    #[account(mut)]
+    pub referrer_open_orders: Option<Account<'info, OpenOrders>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct MarketOrderResult {
+    pub order_id: u64,
+    pub filled_amount: u64,
+    pub quote_amount: u64,
+    pub unfilled_amount: u64,
+    pub taker_fee: u64,
+}
+
+// Market orders sweep the opposite side of the book and never rest.
+// worst_price is the highest price a buy will pay or the lowest price a sell will accept.
+// quote_budget caps the quote a buy can spend, taker fee included, and is ignored for sells.
+// Makers' sides of the fills are queued on the event queue for consume_events.
+pub fn handle_create_market_order(
+    context: Context<CreateMarketOrder>,
+    side: u8,
+    self_trade_prevention: u8,
+    amount: u64,
+    worst_price: u64,
+    quote_budget: u64,
+) -> Result<MarketOrderResult> {
+    let order_book = &mut context.accounts.order_book;
+    let user = &context.accounts.user;
+    let order_slab = context.accounts.order_slab.to_account_info();
+    let mut order_slab_data = order_slab.try_borrow_mut_data()?;
+    let mut book = Book::load(&mut order_slab_data)?;
+    let clock = Clock::get()?;
+
+    // Validate inputs
+    if amount == 0 {
+        return err!(ErrorCode::InvalidOrderAmount);
+    }
+
+    if worst_price == 0 {
+        return err!(ErrorCode::InvalidOrderPrice);
+    }
+
+    // worst_price is only a bound and may sit between ticks
+    order_book.validate_amount(amount)?;
+
+    let side_enum = match side {
+        0 => Side::Buy,
+        1 => Side::Sell,
+        _ => return err!(ErrorCode::InvalidOrderSide),
+    };
+
+    let self_trade_prevention = match self_trade_prevention {
+        0 => SelfTradePrevention::CancelTaker,
+        1 => SelfTradePrevention::CancelMaker,
+        2 => SelfTradePrevention::CancelBoth,
+        3 => SelfTradePrevention::DecrementAndCancel,
+        _ => return err!(ErrorCode::InvalidSelfTradePrevention),
+    };
+
+    let order_id = order_book.next_order_id();
+    msg!("Creating market order with ID: {}", order_id);
+
+    let mut order = Order {
+        id: order_id,
+        owner: user.key(),
+        side: side_enum,
+        price: worst_price,
+        original_amount: amount,
+        remaining_amount: amount,
+        created_at: clock.unix_timestamp,
+        self_trade_prevention,
+        // Market orders never rest, so they never pay a maker fee
+        maker_fee_bps: 0,
+    };
+    emit!(OrderPlaced {
+        order_book: order_book.key(),
+        order_id,
+        owner: user.key(),
+        side: side_enum,
+        price: worst_price,
+        amount,
+        market: true,
+        timestamp: clock.unix_timestamp,
+    });
+
+    // Fills are settled against the user's balance as they happen
+    let mut event_queue = context.accounts.event_queue.load_mut()?;
+    let mut trade_history = context.accounts.trade_history.load_mut()?;
+    let mut candles = context.accounts.candles.load_mut()?;
+    let mut traders = Traders::load(
+        &mut context.accounts.open_orders,
+        order_book,
+        context.accounts.referrer_open_orders.as_deref_mut(),
+        &mut event_queue,
+        MarketHistory { trades: &mut trade_history, candles: &mut candles },
+        TakerFunds::Free,
+        &clock,
+    )?;
+    place_market_order(&mut book, &mut traders, &mut order, quote_budget)
+}
+
+// Check the taker can cover a market order, sweep the book with it and report what was
+// left unfilled. The unfilled part was never taken from the balance, so it is simply dropped.
+pub(crate) fn place_market_order(
+    book: &mut Book,
+    traders: &mut Traders,
+    order: &mut Order,
+    quote_budget: u64,
+) -> Result<MarketOrderResult> {
+    let balances = &traders.get_mut(&order.owner)?.balances;
+    let quote_limit = match order.side {
+        Side::Buy => {
+            require!(quote_budget > 0, ErrorCode::InvalidQuoteBudget);
+            require!(balances.quote_free >= quote_budget, ErrorCode::InsufficientBalance);
+            Some(quote_budget)
+        },
+        Side::Sell => {
+            require!(balances.base_free >= order.original_amount, ErrorCode::InsufficientBalance);
+            None
+        }
+    };
+
+    let outcome = book.match_order(traders, order, quote_limit)?;
+    let unfilled_amount = order.original_amount.checked_sub(outcome.filled_amount)
+        .ok_or(ErrorCode::CalculationFailure)?;
+    if unfilled_amount > 0 {
+        emit!(OrderCancelled {
+            order_book: traders.order_book_key(),
+            order_id: order.id,
+            owner: order.owner,
+            side: order.side,
+            cancelled_amount: unfilled_amount,
+            remaining_amount: 0,
+        });
+    }
+
+    msg!("Market order {} filled: {}, quote: {}, unfilled: {}, fee: {}",
+        order.id, outcome.filled_amount, outcome.filled_quote, unfilled_amount, outcome.taker_fee);
+
+    Ok(MarketOrderResult {
+        order_id: order.id,
+        filled_amount: outcome.filled_amount,
+        quote_amount: outcome.filled_quote,
+        unfilled_amount,
+        taker_fee: outcome.taker_fee,
+    })
+}
@@ -0,0 +1,74 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::constants::FEE_VOLUME_DAYS;
+use crate::state::{Balances, Book, EventQueue, OpenOrders, Orderbook, OrderSlab};
+
+#[derive(Accounts)]
+pub struct CreateOpenOrders<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Only used as the owner the open orders account is created for
+    pub owner: UncheckedAccount<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut,
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+
+    #[account(mut,
+        seeds = [b"order_slab", order_book.key().as_ref()],
+        bump = order_slab.load()?.bump
+    )]
+    pub order_slab: AccountLoader<'info, OrderSlab>,
+
+    #[account(
+        seeds = [b"event_queue", order_book.key().as_ref()],
+        bump = event_queue.load()?.bump
+    )]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+
+    #[account(init,
+        payer = payer,
+        space = 8 + OpenOrders::INIT_SPACE,
+        seeds = [b"open_orders", order_book.key().as_ref(), owner.key().as_ref()],
+        bump)]
+    pub open_orders: Account<'info, OpenOrders>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// Anyone can pay for a user's open orders account, so a maker who has not traded since
+// balances moved out of the orderbook can't hold up consume_events settling their fills
+pub fn handle_create_open_orders(context: Context<CreateOpenOrders>) -> Result<()> {
+    let owner = context.accounts.owner.key();
+    let order_book = &mut context.accounts.order_book;
+    let order_slab = context.accounts.order_slab.to_account_info();
+    let mut order_slab_data = order_slab.try_borrow_mut_data()?;
+    let book = Book::load(&mut order_slab_data)?;
+
+    let open_orders = &mut context.accounts.open_orders;
+    open_orders.set_inner(OpenOrders {
+        order_book: order_book.key(),
+        owner,
+        balances: Balances::default(),
+        order_ids: Vec::new(),
+        bump: context.bumps.open_orders,
+        daily_volume: [0; FEE_VOLUME_DAYS],
+        volume_day: 0,
+    });
+
+    // Carry over what the user had in the orderbook's legacy balances, along with the
+    // funds held by any orders they placed before this account existed
+    let event_queue = context.accounts.event_queue.load()?;
+    open_orders.claim_legacy_funds(order_book, &book, &event_queue)?;
+
+    msg!("Created open orders account for {}, free base: {}, quote: {}, resting orders: {}",
+        owner, open_orders.balances.base_free, open_orders.balances.quote_free, open_orders.order_ids.len());
+    Ok(())
+}
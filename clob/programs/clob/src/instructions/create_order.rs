@@ -3,8 +3,10 @@ use anchor_lang::prelude::*;
 use anchor_spl::token_interface::Mint;
 
 
-use crate::state::{Orderbook, Order, Side};
+use crate::state::{Book, Candles, EventQueue, MarketHistory, OpenOrders, Orderbook, OrderSlab, TradeHistory, Traders, TakerFunds, Order, SelfTradePrevention, Side, TimeInForce};
 use crate::error::ErrorCode;
+use crate::events::{OrderCancelled, OrderPlaced};
+use crate::instructions::match_order::MatchingResult;
 
 #[derive(Accounts)]
 pub struct CreateOrder<'info> {
@@ -20,12 +22,57 @@ pub struct CreateOrder<'info> {
     )]
     pub order_book: Account<'info, Orderbook>,
 
+    #[account(mut,
+        seeds = [b"order_slab", order_book.key().as_ref()],
+        bump = order_slab.load()?.bump
+    )]
+    pub order_slab: AccountLoader<'info, OrderSlab>,
+
+    #[account(mut,
+        seeds = [b"event_queue", order_book.key().as_ref()],
+        bump = event_queue.load()?.bump
+    )]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+
+    #[account(mut,
+        seeds = [b"trade_history", order_book.key().as_ref()],
+        bump = trade_history.load()?.bump
+    )]
+    pub trade_history: AccountLoader<'info, TradeHistory>,
+
+    #[account(mut,
+        seeds = [b"candles", order_book.key().as_ref()],
+        bump = candles.load()?.bump
+    )]
+    pub candles: AccountLoader<'info, Candles>,
+
+    #[account(mut,
+        seeds = [b"open_orders", order_book.key().as_ref(), user.key().as_ref()],
+        bump = open_orders.bump
+    )]
+    pub open_orders: Account<'info, OpenOrders>,
+
+    // Open orders account of whoever referred the user, credited with a share of the taker fee
+    #[account(mut)]
+    pub referrer_open_orders: Option<Account<'info, OpenOrders>>,
+
     pub system_program: Program<'info, System>,
 }
 
-pub fn handle_create_order(context: Context<CreateOrder>, side: u8, price: u64, amount: u64) -> Result<()> {
+// Makers' sides of the fills are queued on the event queue for consume_events
+pub fn handle_create_order(
+    context: Context<CreateOrder>,
+    side: u8,
+    time_in_force: u8,
+    self_trade_prevention: u8,
+    mut price: u64,
+    amount: u64,
+) -> Result<MatchingResult> {
     let order_book = &mut context.accounts.order_book;
     let user = &context.accounts.user;
+    let order_slab = context.accounts.order_slab.to_account_info();
+    let mut order_slab_data = order_slab.try_borrow_mut_data()?;
+    let mut book = Book::load(&mut order_slab_data)?;
     let clock = Clock::get()?;
     
     // Validate inputs
@@ -37,17 +84,71 @@ pub fn handle_create_order(context: Context<CreateOrder>, side: u8, price: u64,
         return err!(ErrorCode::InvalidOrderPrice);
     }
 
+    order_book.validate_price(price)?;
+    order_book.validate_amount(amount)?;
+
     let side_enum = match side {
         0 => Side::Buy,
         1 => Side::Sell,
         _ => return err!(ErrorCode::InvalidOrderSide),
     };
+
+    let time_in_force = match time_in_force {
+        0 => TimeInForce::GoodTillCancelled,
+        1 => TimeInForce::ImmediateOrCancel,
+        2 => TimeInForce::FillOrKill,
+        3 => TimeInForce::PostOnly,
+        4 => TimeInForce::PostOnlySlide,
+        _ => return err!(ErrorCode::InvalidTimeInForce),
+    };
+
+    let self_trade_prevention = match self_trade_prevention {
+        0 => SelfTradePrevention::CancelTaker,
+        1 => SelfTradePrevention::CancelMaker,
+        2 => SelfTradePrevention::CancelBoth,
+        3 => SelfTradePrevention::DecrementAndCancel,
+        _ => return err!(ErrorCode::InvalidSelfTradePrevention),
+    };
+
+    // Post-only orders must not take liquidity: reject them, or slide them one tick
+    // behind the best opposite price, if they would cross the book
+    if time_in_force == TimeInForce::PostOnly || time_in_force == TimeInForce::PostOnlySlide {
+        let crossed_price = match side_enum {
+            Side::Buy => book.best_ask().filter(|best_ask| price >= *best_ask),
+            Side::Sell => book.best_bid().filter(|best_bid| price <= *best_bid),
+        };
+
+        if let Some(crossed_price) = crossed_price {
+            if time_in_force == TimeInForce::PostOnly {
+                return err!(ErrorCode::WouldCrossBook);
+            }
+            price = match side_enum {
+                Side::Buy => crossed_price.checked_sub(order_book.tick()).filter(|slid| *slid > 0)
+                    .ok_or(ErrorCode::WouldCrossBook)?,
+                Side::Sell => crossed_price.checked_add(order_book.tick())
+                    .ok_or(ErrorCode::CalculationFailure)?,
+            };
+            msg!("Post-only order re-priced to {}", price);
+        }
+    }
+
+    // Fill-or-kill orders must be completely fillable before anything is matched
+    if time_in_force == TimeInForce::FillOrKill
+        && book.fillable_amount(side_enum, price)? < amount
+    {
+        return err!(ErrorCode::FillOrKillNotFilled);
+    }
     
     // Create the new order
+    // The order pays the maker fee of the user's current tier on every fill, and a resting
+    // buy reserves it on top of its quote; rebates need no reserve
+    let volume = context.accounts.open_orders.current_volume(clock.unix_timestamp);
+    let (maker_fee_bps, _) = order_book.fee_rates(volume);
+
     let order_id = order_book.next_order_id();
     msg!("Creating new order with ID: {}", order_id);
     
-    let new_order = Order {
+    let mut new_order = Order {
         id: order_id,
         owner: user.key(),
         side: side_enum,
@@ -55,31 +156,154 @@ pub fn handle_create_order(context: Context<CreateOrder>, side: u8, price: u64,
         original_amount: amount,
         remaining_amount: amount,
         created_at: clock.unix_timestamp,
+        self_trade_prevention,
+        maker_fee_bps,
     };
+    let order_book_key = order_book.key();
+    emit!(OrderPlaced {
+        order_book: order_book_key,
+        order_id,
+        owner: user.key(),
+        side: side_enum,
+        price,
+        amount,
+        market: false,
+        timestamp: clock.unix_timestamp,
+    });
     
-    // Transfer tokens to the vault based on order side
-    match side_enum {
-        Side::Buy => {
-            // For buy orders, transfer quote tokens (e.g., USDC)
-            let quote_amount = price.checked_mul(amount)
-                .ok_or(ErrorCode::CalculationFailure)?;
-
-            if order_book.buys.len() >= Orderbook::MAX_ORDERS {
-                return err!(ErrorCode::OrderbookFull);
-            }
-            order_book.subtract_balance(&user.key(), 0, quote_amount)?;
-            msg!("Added buy order {} to orderbook at price {}", order_id, price);
-            order_book.buys.push(new_order);
-        },
-        Side::Sell => {
-            if order_book.sells.len() >= Orderbook::MAX_ORDERS {
-                return err!(ErrorCode::OrderbookFull);
+    // Escrow the whole order, buy orders in quote tokens (e.g., USDC) along with their taker
+    // fee and sell orders in base tokens, then cross it against the opposite side. Fills are
+    // paid out of the escrow and whatever is left stays locked for the part that rests.
+    let mut event_queue = context.accounts.event_queue.load_mut()?;
+    let mut trade_history = context.accounts.trade_history.load_mut()?;
+    let mut candles = context.accounts.candles.load_mut()?;
+    let mut traders = Traders::load(
+        &mut context.accounts.open_orders,
+        order_book,
+        context.accounts.referrer_open_orders.as_deref_mut(),
+        &mut event_queue,
+        MarketHistory { trades: &mut trade_history, candles: &mut candles },
+        TakerFunds::Locked,
+        &clock,
+    )?;
+    place_limit_order(&mut book, &mut traders, &mut new_order, time_in_force)
+}
+
+// Escrow a limit order, match it against the book and then rest or cancel whatever is left
+// of it as its time in force says
+pub(crate) fn place_limit_order(
+    book: &mut Book,
+    traders: &mut Traders,
+    order: &mut Order,
+    time_in_force: TimeInForce,
+) -> Result<MatchingResult> {
+    // Only an order that crosses the book when placed can pay a taker fee
+    let can_take = match order.side {
+        Side::Buy => book.best_ask().is_some_and(|best_ask| order.price >= best_ask),
+        Side::Sell => book.best_bid().is_some_and(|best_bid| order.price <= best_bid),
+    };
+    let post_only = matches!(time_in_force, TimeInForce::PostOnly | TimeInForce::PostOnlySlide);
+    traders.escrow(order, can_take && !post_only)?;
+    let outcome = book.match_order(traders, order, None)?;
+    traders.release_escrow_surplus()?;
+    let filled_amount = outcome.filled_amount;
+    let mut rested_amount = 0;
+    let mut cancelled_amount = outcome.cancelled_amount;
+
+    // Self-trade prevention can cancel part of a fill-or-kill order without leaving a remainder
+    if time_in_force == TimeInForce::FillOrKill && filled_amount < order.original_amount {
+        return err!(ErrorCode::FillOrKillNotFilled);
+    }
+
+    if order.remaining_amount > 0 {
+        match time_in_force {
+            TimeInForce::GoodTillCancelled | TimeInForce::PostOnly | TimeInForce::PostOnlySlide => {
+                traders.get_mut(&order.owner)?.track_order(order.id)?;
+                book.insert_order(*order)?;
+                rested_amount = order.remaining_amount;
+                match order.side {
+                    Side::Buy => msg!("Added buy order {} to orderbook at price {}", order.id, order.price),
+                    Side::Sell => msg!("Added sell order {} to orderbook at price {}", order.id, order.price),
+                }
+            },
+            // Cancelling the unfilled part returns its escrow to the user's balance
+            TimeInForce::ImmediateOrCancel => {
+                traders.release_taker(order, order.remaining_amount)?;
+                cancelled_amount = cancelled_amount.checked_add(order.remaining_amount)
+                    .ok_or(ErrorCode::CalculationFailure)?;
+            },
+            TimeInForce::FillOrKill => {
+                return err!(ErrorCode::FillOrKillNotFilled);
             }
-            order_book.subtract_balance(&user.key(), amount, 0)?;
-            msg!("Added sell order {} to orderbook at price {}", order_id, price);
-            order_book.sells.push(new_order);
         }
     }
-    
-    Ok(())
-}
\ No newline at end of file
+
+    if cancelled_amount > 0 {
+        emit!(OrderCancelled {
+            order_book: traders.order_book_key(),
+            order_id: order.id,
+            owner: order.owner,
+            side: order.side,
+            cancelled_amount,
+            remaining_amount: rested_amount,
+        });
+    }
+
+    msg!("Order {} filled: {}, rested: {}, cancelled: {}, fee: {}",
+        order.id, filled_amount, rested_amount, cancelled_amount, outcome.taker_fee);
+
+    Ok(MatchingResult {
+        order_id: order.id,
+        filled_amount,
+        rested_amount,
+        cancelled_amount,
+        taker_fee: outcome.taker_fee,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::state::test_market::Market;
+    use crate::state::{Side, TimeInForce};
+
+    #[test]
+    fn fully_filled_order_rests_nothing() {
+        let mut market = Market::new(0, 0, 2);
+        market.deposit(0, 0, 1_000);
+        market.deposit(1, 10, 0);
+        market.rest(1, Side::Sell, 10, 10);
+
+        let result = market.limit(0, Side::Buy, 10, 6, TimeInForce::GoodTillCancelled).unwrap();
+        assert_eq!((result.filled_amount, result.rested_amount, result.cancelled_amount), (6, 0, 0));
+        assert!(market.user(0).order_ids.is_empty());
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn partially_filled_order_rests_the_remainder() {
+        let mut market = Market::new(0, 0, 2);
+        market.deposit(0, 0, 1_000);
+        market.deposit(1, 4, 0);
+        market.rest(1, Side::Sell, 10, 4);
+
+        let result = market.limit(0, Side::Buy, 10, 10, TimeInForce::GoodTillCancelled).unwrap();
+        assert_eq!((result.filled_amount, result.rested_amount, result.cancelled_amount), (4, 6, 0));
+        assert_eq!(market.user(0).order_ids, vec![result.order_id]);
+        assert_eq!(market.book().order_at(Side::Buy, 0).remaining_amount, 6);
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn order_that_does_not_cross_rests_whole() {
+        let mut market = Market::new(0, 0, 2);
+        market.deposit(0, 0, 1_000);
+        market.deposit(1, 10, 0);
+        market.rest(1, Side::Sell, 12, 10);
+
+        let result = market.limit(0, Side::Buy, 10, 5, TimeInForce::GoodTillCancelled).unwrap();
+        assert_eq!((result.filled_amount, result.rested_amount, result.cancelled_amount), (0, 5, 0));
+        assert_eq!(result.taker_fee, 0);
+        assert_eq!(market.user(0).balances.quote_locked, 50);
+        market.assert_conserved();
+    }
+}
@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::state::{Orderbook, TradeHistory};
+
+// Adds the trade history to an orderbook created before matching recorded its fills.
+// Anyone can pay for it.
+#[derive(Accounts)]
+pub struct CreateTradeHistory<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+
+    #[account(init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<TradeHistory>(),
+        seeds = [b"trade_history", order_book.key().as_ref()],
+        bump)]
+    pub trade_history: AccountLoader<'info, TradeHistory>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_create_trade_history(context: Context<CreateTradeHistory>) -> Result<()> {
+    context.accounts.trade_history.load_init()?
+        .initialize(context.accounts.order_book.key(), context.bumps.trade_history);
+    msg!("Created trade history {} for orderbook {}", context.accounts.trade_history.key(), context.accounts.order_book.key());
+    Ok(())
+}
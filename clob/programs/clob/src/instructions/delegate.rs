@@ -1,7 +1,8 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::Mint;
 
-use crate::state::Orderbook;
+use crate::events::Delegated;
+use crate::state::{Candles, EventQueue, Orderbook, OrderSlab, TradeHistory};
 
 use ephemeral_rollups_sdk::anchor::delegate;
 use ephemeral_rollups_sdk::cpi::DelegateConfig;
@@ -21,6 +22,34 @@ pub struct DelegateOrderbook<'info> {
         bump
     )]
     pub order_book: Account<'info, Orderbook>,//TODO: check this since doc says AccountInfo, I used Account to fix the error
+
+    #[account(mut,
+        del,
+        seeds = [b"order_slab", order_book.key().as_ref()],
+        bump
+    )]
+    pub order_slab: AccountLoader<'info, OrderSlab>,
+
+    #[account(mut,
+        del,
+        seeds = [b"event_queue", order_book.key().as_ref()],
+        bump
+    )]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+
+    #[account(mut,
+        del,
+        seeds = [b"trade_history", order_book.key().as_ref()],
+        bump
+    )]
+    pub trade_history: AccountLoader<'info, TradeHistory>,
+
+    #[account(mut,
+        del,
+        seeds = [b"candles", order_book.key().as_ref()],
+        bump
+    )]
+    pub candles: AccountLoader<'info, Candles>,
 }
 
 pub fn handle_delegate(ctx: Context<DelegateOrderbook>) -> Result<()> {
@@ -29,5 +58,30 @@ pub fn handle_delegate(ctx: Context<DelegateOrderbook>) -> Result<()> {
         &[b"orderbook", ctx.accounts.base_token_mint.key().as_ref(), ctx.accounts.quote_token_mint.key().as_ref()],
         DelegateConfig::default(),
     )?;
+    ctx.accounts.delegate_order_slab(
+        &ctx.accounts.user,
+        &[b"order_slab", ctx.accounts.order_book.key().as_ref()],
+        DelegateConfig::default(),
+    )?;
+    ctx.accounts.delegate_event_queue(
+        &ctx.accounts.user,
+        &[b"event_queue", ctx.accounts.order_book.key().as_ref()],
+        DelegateConfig::default(),
+    )?;
+    ctx.accounts.delegate_trade_history(
+        &ctx.accounts.user,
+        &[b"trade_history", ctx.accounts.order_book.key().as_ref()],
+        DelegateConfig::default(),
+    )?;
+    ctx.accounts.delegate_candles(
+        &ctx.accounts.user,
+        &[b"candles", ctx.accounts.order_book.key().as_ref()],
+        DelegateConfig::default(),
+    )?;
+    emit!(Delegated {
+        order_book: ctx.accounts.order_book.key(),
+        open_orders: None,
+        signer: ctx.accounts.user.key(),
+    });
     Ok(())
 }
@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::events::Delegated;
+use crate::state::OpenOrders;
+
+use ephemeral_rollups_sdk::anchor::delegate;
+use ephemeral_rollups_sdk::cpi::DelegateConfig;
+
+#[delegate]
+#[derive(Accounts)]
+pub struct DelegateOpenOrders<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Only used to derive the open orders address; the orderbook may already be delegated
+    #[account(seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()], bump)]
+    pub order_book: UncheckedAccount<'info>,
+
+    #[account(mut,
+        del,
+        seeds = [b"open_orders", order_book.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub open_orders: Account<'info, OpenOrders>,
+}
+
+pub fn handle_delegate_open_orders(ctx: Context<DelegateOpenOrders>) -> Result<()> {
+    ctx.accounts.delegate_open_orders(
+        &ctx.accounts.user,
+        &[b"open_orders", ctx.accounts.order_book.key().as_ref(), ctx.accounts.user.key().as_ref()],
+        DelegateConfig::default(),
+    )?;
+    emit!(Delegated {
+        order_book: ctx.accounts.order_book.key(),
+        open_orders: Some(ctx.accounts.open_orders.key()),
+        signer: ctx.accounts.user.key(),
+    });
+    Ok(())
+}
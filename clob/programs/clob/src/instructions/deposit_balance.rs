@@ -5,8 +5,9 @@ use anchor_spl::{
     token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
-use crate::state::Orderbook;
+use crate::state::{OpenOrders, Orderbook};
 use crate::instructions::shared::transfer_tokens;
+use crate::events::Deposit;
 
 #[derive(Accounts)]
 pub struct DepositBalance<'info> {
@@ -22,6 +23,12 @@ pub struct DepositBalance<'info> {
     )]
     pub order_book: Account<'info, Orderbook>,
 
+    #[account(mut,
+        seeds = [b"open_orders", order_book.key().as_ref(), user.key().as_ref()],
+        bump = open_orders.bump
+    )]
+    pub open_orders: Account<'info, OpenOrders>,
+
     #[account(init_if_needed,
         payer = user,
         associated_token::mint = base_token_mint,
@@ -58,6 +65,12 @@ pub struct DepositBalance<'info> {
 }
 
 pub fn handle_deposit_balance(context: Context<DepositBalance>, quote_amount: u64, base_amount: u64) -> Result<()> {
+    let mut deposited = Deposit {
+        order_book: context.accounts.order_book.key(),
+        owner: context.accounts.user.key(),
+        base_amount: 0,
+        quote_amount: 0,
+    };
     if quote_amount > 0 && context.accounts.user_quote_account.amount > 0 {
         transfer_tokens(
             &context.accounts.user_quote_account,
@@ -68,7 +81,8 @@ pub fn handle_deposit_balance(context: Context<DepositBalance>, quote_amount: u6
             &context.accounts.token_program,
             None
         )?;
-        context.accounts.order_book.add_balance(&context.accounts.user.key(), 0, quote_amount)?;
+        context.accounts.open_orders.deposit(&mut context.accounts.order_book, 0, quote_amount)?;
+        deposited.quote_amount = quote_amount;
     }
     if base_amount > 0 && context.accounts.user_base_account.amount > 0 {
         transfer_tokens(
@@ -80,7 +94,17 @@ pub fn handle_deposit_balance(context: Context<DepositBalance>, quote_amount: u6
             &context.accounts.token_program,
             None
         )?;
-        context.accounts.order_book.add_balance(&context.accounts.user.key(), base_amount, 0)?;
+        context.accounts.open_orders.deposit(&mut context.accounts.order_book, base_amount, 0)?;
+        deposited.base_amount = base_amount;
     }
+
+    context.accounts.base_vault.reload()?;
+    context.accounts.quote_vault.reload()?;
+    context.accounts.order_book.check_vault_balances(
+        context.accounts.base_vault.amount,
+        context.accounts.quote_vault.amount,
+    )?;
+
+    emit!(deposited);
     Ok(())
 }
\ No newline at end of file
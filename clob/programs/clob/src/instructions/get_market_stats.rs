@@ -0,0 +1,97 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::state::{Candles, Orderbook};
+
+#[derive(Accounts)]
+pub struct GetMarketStats<'info> {
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+
+    #[account(
+        seeds = [b"candles", order_book.key().as_ref()],
+        bump = candles.load()?.bump
+    )]
+    pub candles: AccountLoader<'info, Candles>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct MarketStats {
+    pub last_price: u64,
+    pub base_volume: u128,
+    pub quote_volume: u128,
+    pub trade_count: u64,
+    pub base_volume_24h: u64,
+    pub quote_volume_24h: u64,
+    // Base held by resting sells, and quote held by resting buys including their maker
+    // fee reserves
+    pub resting_base: u64,
+    pub resting_quote: u64,
+}
+
+impl MarketStats {
+    // Stats of an orderbook as of `now`, which sets the 24 hour window
+    pub fn new(order_book: &Orderbook, candles: &Candles, now: i64) -> Self {
+        let (base_volume_24h, quote_volume_24h) = candles.volume_24h(now);
+        MarketStats {
+            last_price: order_book.last_price,
+            base_volume: order_book.base_volume,
+            quote_volume: order_book.quote_volume,
+            trade_count: order_book.trade_count,
+            base_volume_24h,
+            quote_volume_24h,
+            resting_base: order_book.totals.base_locked,
+            resting_quote: order_book.totals.quote_locked,
+        }
+    }
+}
+
+// Read-only view for market listings, meant to be simulated. The stats come back as the
+// instruction's return data.
+pub fn handle_get_market_stats(context: Context<GetMarketStats>) -> Result<MarketStats> {
+    let candles = context.accounts.candles.load()?;
+    Ok(MarketStats::new(&context.accounts.order_book, &candles, Clock::get()?.unix_timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_market::Market;
+    use crate::state::{Side, TimeInForce};
+
+    #[test]
+    fn stats_cover_trades_volumes_and_resting_funds() {
+        let mut market = Market::new(2, 0, 2);
+        market.deposit(0, 0, 10_000);
+        market.deposit(1, 100, 0);
+
+        market.clock.unix_timestamp = 0;
+        market.rest(1, Side::Sell, 10, 2);
+        market.limit(0, Side::Buy, 10, 2, TimeInForce::ImmediateOrCancel).unwrap();
+        market.clock.unix_timestamp = 30 * 60 * 60;
+        market.rest(1, Side::Sell, 12, 1);
+        market.limit(0, Side::Buy, 12, 1, TimeInForce::ImmediateOrCancel).unwrap();
+        market.rest(1, Side::Sell, 15, 4);
+        market.rest(0, Side::Buy, 9_000, 1);
+
+        let stats = MarketStats::new(&market.order_book, &market.candles, market.clock.unix_timestamp);
+        assert_eq!(stats.last_price, 12);
+        assert_eq!((stats.base_volume, stats.quote_volume), (3, 32));
+        assert_eq!(stats.trade_count, 2);
+        // The first fill is more than a day old
+        assert_eq!((stats.base_volume_24h, stats.quote_volume_24h), (1, 12));
+        // The resting buy holds its quote and a 2 bps maker fee reserve
+        assert_eq!((stats.resting_base, stats.resting_quote), (4, 9_002));
+
+        // A day after the last fill only the totals are left
+        let stats = MarketStats::new(&market.order_book, &market.candles, market.clock.unix_timestamp + 25 * 60 * 60);
+        assert_eq!((stats.base_volume_24h, stats.quote_volume_24h), (0, 0));
+        assert_eq!((stats.base_volume, stats.trade_count), (3, 2));
+    }
+}
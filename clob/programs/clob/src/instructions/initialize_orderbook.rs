@@ -4,9 +4,11 @@ use anchor_spl::{
     token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
-use crate::state::Orderbook;
+use crate::state::{Balances, Book, Candles, EventQueue, Orderbook, OrderSlab, TradeHistory};
+use crate::error::ErrorCode;
 
 #[derive(Accounts)]
+#[instruction(order_capacity: u16)]
 pub struct InitializeOrderbook<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -29,29 +31,75 @@ pub struct InitializeOrderbook<'info> {
         )]
     pub quote_vault: InterfaceAccount<'info, TokenAccount>,
     
-    #[account(init_if_needed,
+    // init rather than init_if_needed, so an existing book, legacy ones waiting for
+    // migrate_orderbook included, can't be initialized again over its deposits
+    #[account(init,
         payer = payer,
         space = 8 + Orderbook::INIT_SPACE,
         seeds = [b"orderbook",base_token_mint.key().as_ref(),quote_token_mint.key().as_ref()],
         bump)]
     pub order_book: Account<'info, Orderbook>,
 
+    #[account(init,
+        payer = payer,
+        space = OrderSlab::space(order_capacity),
+        seeds = [b"order_slab", order_book.key().as_ref()],
+        bump)]
+    pub order_slab: AccountLoader<'info, OrderSlab>,
+
+    #[account(init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<EventQueue>(),
+        seeds = [b"event_queue", order_book.key().as_ref()],
+        bump)]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+
+    #[account(init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<TradeHistory>(),
+        seeds = [b"trade_history", order_book.key().as_ref()],
+        bump)]
+    pub trade_history: AccountLoader<'info, TradeHistory>,
+
+    #[account(init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<Candles>(),
+        seeds = [b"candles", order_book.key().as_ref()],
+        bump)]
+    pub candles: AccountLoader<'info, Candles>,
+
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
-pub fn init_orderbook(context: Context<InitializeOrderbook>) -> Result<()> {
+// Prices are given in quote atoms per base lot of base_lot_size atoms, on a grid of tick_size
+pub fn init_orderbook(
+    context: Context<InitializeOrderbook>,
+    order_capacity: u16,
+    tick_size: u64,
+    base_lot_size: u64,
+    min_order_size: u64,
+) -> Result<()> {
+    require!(order_capacity > 0, ErrorCode::InvalidOrderCapacity);
+    Orderbook::validate_market_params(tick_size, base_lot_size, min_order_size)?;
+
     msg!("Initializing orderbook");
     msg!("Base token mint: {}", context.accounts.base_token_mint.key());
     msg!("Quote token mint: {}", context.accounts.quote_token_mint.key());  
     msg!("Base vault: {}", context.accounts.base_vault.key());
     msg!("Quote vault: {}", context.accounts.quote_vault.key());    
     msg!("Order book: {}", context.accounts.order_book.key());
+    msg!("Order slab: {}", context.accounts.order_slab.key());
+    msg!("Event queue: {}", context.accounts.event_queue.key());
+    msg!("Trade history: {}", context.accounts.trade_history.key());
+    msg!("Candles: {}", context.accounts.candles.key());
     msg!("Payer: {}", context.accounts.payer.key());
     msg!("Associated token program: {}", context.accounts.associated_token_program.key());
     msg!("Token program: {}", context.accounts.token_program.key());
     msg!("System program: {}", context.accounts.system_program.key());
+    msg!("Order capacity: {}", order_capacity);
+    msg!("Tick size: {}, base lot size: {}, min order size: {}", tick_size, base_lot_size, min_order_size);
     context.accounts.order_book.set_inner(Orderbook {
         base_asset: context.accounts.base_token_mint.key(),
         quote_asset: context.accounts.quote_token_mint.key(),
@@ -59,13 +107,35 @@ pub fn init_orderbook(context: Context<InitializeOrderbook>) -> Result<()> {
         quote_vault: context.accounts.quote_vault.key(),
         base_decimals: context.accounts.base_token_mint.decimals,
         quote_decimals: context.accounts.quote_token_mint.decimals,
-        buys: Vec::with_capacity(Orderbook::MAX_ORDERS),
-        sells: Vec::with_capacity(Orderbook::MAX_ORDERS),
         authority: context.accounts.payer.key(),
         order_counter: 0,
         bump: context.bumps.order_book,
         user_balances: Vec::with_capacity(20),
         is_delegated: false,
+        order_capacity,
+        totals: Balances::default(),
+        maker_fee_bps: 0,
+        taker_fee_bps: 0,
+        accrued_fees: 0,
+        fee_tiers: Vec::new(),
+        referral_share_bps: 0,
+        tick_size,
+        base_lot_size,
+        min_order_size,
+        last_price: 0,
+        base_volume: 0,
+        quote_volume: 0,
+        trade_count: 0,
     });
+
+    let order_slab = context.accounts.order_slab.to_account_info();
+    let mut order_slab_data = order_slab.try_borrow_mut_data()?;
+    Book::load(&mut order_slab_data)?.initialize(context.accounts.order_book.key(), context.bumps.order_slab);
+    context.accounts.event_queue.load_init()?
+        .initialize(context.accounts.order_book.key(), context.bumps.event_queue);
+    context.accounts.trade_history.load_init()?
+        .initialize(context.accounts.order_book.key(), context.bumps.trade_history);
+    context.accounts.candles.load_init()?
+        .initialize(context.accounts.order_book.key(), context.bumps.candles);
     Ok(())
 }
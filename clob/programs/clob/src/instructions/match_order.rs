@@ -1,8 +1,9 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::Mint;
 
-use crate::state::{Orderbook, Side};
+use crate::state::{Book, Candles, EventQueue, MarketHistory, OpenOrders, Order, Orderbook, OrderSlab, TradeHistory, TakerFunds, Traders};
 use crate::error::ErrorCode;
+use crate::events::OrderCancelled;
 
 #[derive(Accounts)]
 pub struct MatchOrder<'info> {
@@ -17,243 +18,143 @@ pub struct MatchOrder<'info> {
         bump = order_book.bump
     )]
     pub order_book: Account<'info, Orderbook>,
+
+    #[account(mut,
+        seeds = [b"order_slab", order_book.key().as_ref()],
+        bump = order_slab.load()?.bump
+    )]
+    pub order_slab: AccountLoader<'info, OrderSlab>,
+
+    #[account(mut,
+        seeds = [b"event_queue", order_book.key().as_ref()],
+        bump = event_queue.load()?.bump
+    )]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+
+    #[account(mut,
+        seeds = [b"trade_history", order_book.key().as_ref()],
+        bump = trade_history.load()?.bump
+    )]
+    pub trade_history: AccountLoader<'info, TradeHistory>,
+
+    #[account(mut,
+        seeds = [b"candles", order_book.key().as_ref()],
+        bump = candles.load()?.bump
+    )]
+    pub candles: AccountLoader<'info, Candles>,
+
+    #[account(mut,
+        seeds = [b"open_orders", order_book.key().as_ref(), user.key().as_ref()],
+        bump = open_orders.bump
+    )]
+    pub open_orders: Account<'info, OpenOrders>,
     
     pub system_program: Program<'info, System>,
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
 pub struct MatchingResult {
+    pub order_id: u64,
     pub filled_amount: u64,
-    pub remaining_amount: u64,
+    pub rested_amount: u64,
+    pub cancelled_amount: u64,
+    pub taker_fee: u64,
 }
 
+// Makers' sides of the fills are queued on the event queue for consume_events
 pub fn handle_match_order(
     context: Context<MatchOrder>,
     order_id: u64,
 ) -> Result<()> {
     let order_book = &mut context.accounts.order_book;
     let user = &context.accounts.user;
+    let order_slab = context.accounts.order_slab.to_account_info();
+    let mut order_slab_data = order_slab.try_borrow_mut_data()?;
+    let mut book = Book::load(&mut order_slab_data)?;
     
-    msg!("Searching for order ID: {}", order_id);
+    #[cfg(feature = "verbose-logs")]
+    log_state(&book, &context.accounts.open_orders.balances, "before");
 
-    // Log the orderbook state before matching
-    msg!("Orderbook state before matching:");
-    msg!("Number of buys: {}", order_book.buys.len());
-    for (i, bid) in order_book.buys.iter().enumerate() {
-        msg!("Bid #{}: ID: {}, Price: {}, Remaining: {}, Owner: {}", 
-            i, bid.id, bid.price, bid.remaining_amount, bid.owner);
-    }
-    
-    msg!("Number of sells: {}", order_book.sells.len());
-    for (i, ask) in order_book.sells.iter().enumerate() {
-        msg!("Ask #{}: ID: {}, Price: {}, Remaining: {}, Owner: {}", 
-            i, ask.id, ask.price, ask.remaining_amount, ask.owner);
-    }
-    
-    // Log user balances in the orderbook
-    msg!("User balances before matching:");
-    for (i, balance) in order_book.user_balances.iter().enumerate() {
-        msg!("Balance #{}: User: {}, Base: {}, Quote: {}", 
-            i, balance.owner, balance.base_amount, balance.quote_amount);
-    }
-    
-    // Find the order by ID
-    let mut order_index = None;
-    let mut order_side = None;
+    // Match the order against the opposite side of the book. The order already locked its
+    // funds, so fills are paid from that lock and a buy filled below its limit gets the
+    // difference back, as does any part cancelled by self-trade prevention.
+    let mut event_queue = context.accounts.event_queue.load_mut()?;
+    let mut trade_history = context.accounts.trade_history.load_mut()?;
+    let mut candles = context.accounts.candles.load_mut()?;
+    let mut traders = Traders::load(
+        &mut context.accounts.open_orders,
+        order_book,
+        None,
+        &mut event_queue,
+        MarketHistory { trades: &mut trade_history, candles: &mut candles },
+        TakerFunds::Locked,
+        &Clock::get()?,
+    )?;
+    rematch_order(&mut book, &mut traders, order_id, &user.key())?;
 
-    // Search in buys
-    for (i, bid) in order_book.buys.iter().enumerate() {
-        msg!("Bid ID iter id: {}", bid.id);
-        if bid.id == order_id {
-            if bid.owner != user.key() {
-                return err!(ErrorCode::NotOrderOwner);
-            }
-            order_index = Some(i);
-            order_side = Some(Side::Buy);
-            break;
-        }
-    }
-    
-    // If not found in buys, search in sells
-    if order_index.is_none() {
-        for (i, ask) in order_book.sells.iter().enumerate() {
-            msg!("Ask ID: {}", ask.id);
-            if ask.id == order_id {
-                if ask.owner != user.key() {
-                    return err!(ErrorCode::NotOrderOwner);
-                }
-                order_index = Some(i);
-                order_side = Some(Side::Sell);
-                break;
-            }
-        }
-    }
-    
-    // Return error if order not found
-    let (order_index, order_side) = match (order_index, order_side) {
-        (Some(idx), Some(side)) => (idx, side),
-        _ => {
+    #[cfg(feature = "verbose-logs")]
+    log_state(&book, &context.accounts.open_orders.balances, "after");
+
+    Ok(())
+}
+
+// Take a resting order of `owner` off the book, match it against the opposite side and
+// put back whatever is left of it. Returns the order as it stands after matching.
+pub(crate) fn rematch_order(book: &mut Book, traders: &mut Traders, order_id: u64, owner: &Pubkey) -> Result<Order> {
+    let (side, index) = match book.find_order(order_id) {
+        Some(location) => location,
+        None => {
             msg!("Order not found with ID: {}", order_id);
             return err!(ErrorCode::OrderNotFound);
         }
     };
-    
-    // Get the order
-    let mut order = match order_side {
-        Side::Buy => order_book.buys.remove(order_index),
-        Side::Sell => order_book.sells.remove(order_index),
-    };
-
-    // Match the order - Inline implementation instead of separate function
-    let mut filled_amount: u64 = 0;
-    let mut remaining_amount: u64 = order.remaining_amount;
-
-    match order.side {
-        Side::Buy => {
-            // For buy orders, match against sells (sell orders)
-            // Sort sells by price (lowest first)
-            order_book.sells.sort_by(|a, b| a.price.cmp(&b.price));
-            
-            let mut i = 0;
-            while i < order_book.sells.len() && remaining_amount > 0 {
-                let ask = &mut order_book.sells[i];
-                let ask_owner = ask.owner;
-                
-                // Check if the buy price is >= the ask price
-                if order.price >= ask.price {
-                    // Calculate the matched amount
-                    let match_amount = remaining_amount.min(ask.remaining_amount);
-                    
-                    if match_amount > 0 {
-                        // Update the order amounts
-                        ask.remaining_amount = ask.remaining_amount.checked_sub(match_amount)
-                            .ok_or(ErrorCode::CalculationFailure)?;
-                        remaining_amount = remaining_amount.checked_sub(match_amount)
-                            .ok_or(ErrorCode::CalculationFailure)?;
-                        filled_amount = filled_amount.checked_add(match_amount)
-                            .ok_or(ErrorCode::CalculationFailure)?;
-                        
-                        // Calculate quote amount
-                        let quote_amount = match_amount.checked_mul(ask.price)
-                            .ok_or(ErrorCode::CalculationFailure)?;
-                        
-                        // Remove filled orders
-                        let remaining_amount = ask.remaining_amount;
+    require!(book.order_at(side, index).owner == *owner, ErrorCode::NotOrderOwner);
+    let mut order = book.remove_order(side, index);
 
-                        // Update balances in the orderbook
-                        order_book.add_balance(&user.key(), match_amount, 0)?;
-                        order_book.subtract_balance(&user.key(), 0, quote_amount)?;
-                        order_book.add_balance(&ask_owner, 0, quote_amount)?;
-                        order_book.subtract_balance(&ask_owner, match_amount, 0)?;
-                        
-                        if remaining_amount == 0 {
-                            order_book.sells.remove(i);
-                            // Don't increment i since we removed an element
-                            continue;
-                        }
-                    }
-                } else {
-                    // No more matching orders (price too high)
-                    break;
-                }
-                
-                i += 1;
-            }
-        },
-        Side::Sell => {
-            // For sell orders, match against buys (buy orders)
-            // Sort buys by price (highest first)
-            order_book.buys.sort_by(|a, b| b.price.cmp(&a.price));
-            
-            let mut i = 0;
-            while i < order_book.buys.len() && remaining_amount > 0 {
-                let bid = &mut order_book.buys[i];
-                
-                // Check if the sell price is <= the bid price
-                if order.price <= bid.price {
-                    // Calculate the matched amount
-                    let match_amount = remaining_amount.min(bid.remaining_amount);
-                    
-                    if match_amount > 0 {
-                        let bid_owner = bid.owner;
-                        // Update the order amounts
-                        bid.remaining_amount = bid.remaining_amount.checked_sub(match_amount)
-                            .ok_or(ErrorCode::CalculationFailure)?;
-                        remaining_amount = remaining_amount.checked_sub(match_amount)
-                            .ok_or(ErrorCode::CalculationFailure)?;
-                        filled_amount = filled_amount.checked_add(match_amount)
-                            .ok_or(ErrorCode::CalculationFailure)?;
-                        
-                        // Calculate quote amount
-                        let quote_amount = match_amount.checked_mul(bid.price)
-                            .ok_or(ErrorCode::CalculationFailure)?;
+    let outcome = book.match_order(traders, &mut order, None)?;
+    msg!("Order {} filled: {}, quote: {}, cancelled: {}, fee: {}",
+        order_id, outcome.filled_amount, outcome.filled_quote, outcome.cancelled_amount, outcome.taker_fee);
 
-                        // Remove filled orders
-                        let remaining_amount = bid.remaining_amount;
-
-                        // Update balances in the orderbook
-                        order_book.add_balance(&user.key(), 0, quote_amount)?;
-                        order_book.subtract_balance(&user.key(), match_amount, 0)?;
-                        order_book.add_balance(&bid_owner, match_amount, 0)?;
-                        order_book.subtract_balance(&bid_owner, 0, quote_amount)?;
-
-                        if remaining_amount == 0 {
-                            order_book.buys.remove(i);
-                            // Don't increment i since we removed an element
-                            continue;
-                        }
-                    }
-                } else {
-                    // No more matching orders (price too low)
-                    break;
-                }
-                
-                i += 1;
-            }
-        }
-    }
-    
-    order.remaining_amount = remaining_amount;
-    
     // If the order wasn't fully filled, add it back to the orderbook
-    if remaining_amount > 0 {
-        match order_side {
-            Side::Buy => {
-                if order_book.buys.len() >= Orderbook::MAX_ORDERS {
-                    return err!(ErrorCode::OrderbookFull);
-                }
-                order_book.buys.push(order);
-            },
-            Side::Sell => {
-                if order_book.sells.len() >= Orderbook::MAX_ORDERS {
-                    return err!(ErrorCode::OrderbookFull);
-                }
-                order_book.sells.push(order);
-            }
-        }
+    if order.remaining_amount > 0 {
+        book.insert_order(order)?;
+    } else {
+        traders.get_mut(owner)?.remove_order_id(order.id);
     }
 
-    
-    
-    // Log the orderbook state after matching
-    msg!("Orderbook state after matching:");
-    msg!("Number of buys: {}", order_book.buys.len());
-    for (i, bid) in order_book.buys.iter().enumerate() {
-        msg!("Bid #{}: ID: {}, Price: {}, Remaining: {}, Owner: {}", 
+    if outcome.cancelled_amount > 0 {
+        emit!(OrderCancelled {
+            order_book: traders.order_book_key(),
+            order_id,
+            owner: order.owner,
+            side: order.side,
+            cancelled_amount: outcome.cancelled_amount,
+            remaining_amount: order.remaining_amount,
+        });
+    }
+    Ok(order)
+}
+
+// Dump the whole book and the user's balances, for debugging; too expensive to log on
+// every match in production
+#[cfg(feature = "verbose-logs")]
+fn log_state(book: &Book, balances: &crate::state::Balances, when: &str) {
+    use crate::state::Side;
+
+    msg!("Orderbook state {} matching:", when);
+    msg!("Number of buys: {}", book.len(Side::Buy));
+    for (i, bid) in book.orders(Side::Buy).enumerate() {
+        msg!("Bid #{}: ID: {}, Price: {}, Remaining: {}, Owner: {}",
             i, bid.id, bid.price, bid.remaining_amount, bid.owner);
     }
-    
-    msg!("Number of sells: {}", order_book.sells.len());
-    for (i, ask) in order_book.sells.iter().enumerate() {
-        msg!("Ask #{}: ID: {}, Price: {}, Remaining: {}, Owner: {}", 
+
+    msg!("Number of sells: {}", book.len(Side::Sell));
+    for (i, ask) in book.orders(Side::Sell).enumerate() {
+        msg!("Ask #{}: ID: {}, Price: {}, Remaining: {}, Owner: {}",
             i, ask.id, ask.price, ask.remaining_amount, ask.owner);
     }
-    
-    // Log user balances after matching
-    msg!("User balances after matching:");
-    for (i, balance) in order_book.user_balances.iter().enumerate() {
-        msg!("Balance #{}: User: {}, Base: {}, Quote: {}", 
-            i, balance.owner, balance.base_amount, balance.quote_amount);
-    }
-    
-    Ok(())
-}
\ No newline at end of file
+
+    msg!("User balances {} matching:", when);
+    msg!("Free base: {}, quote: {}, locked base: {}, quote: {}",
+        balances.base_free, balances.quote_free, balances.base_locked, balances.quote_locked);
+}
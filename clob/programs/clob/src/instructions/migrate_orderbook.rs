@@ -0,0 +1,92 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::token_interface::Mint;
+
+use crate::state::{Book, LegacyOrderbook, Orderbook, OrderSlab};
+use crate::error::ErrorCode;
+
+// Rewrites an orderbook created before order slabs existed in the current layout and
+// moves its resting orders into a new order slab. Those books have no order capacity or
+// market parameters yet, so they are given here, and the capacity has to fit the orders
+// on each side. The account is read in the legacy layout, because the current one would
+// read its new fields from bytes left over from the old order and balance lists.
+#[derive(Accounts)]
+#[instruction(order_capacity: u16)]
+pub struct MigrateOrderbook<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: An orderbook in the legacy layout, checked by seeds and owner and parsed in the handler
+    #[account(mut,
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump,
+        owner = crate::ID
+    )]
+    pub order_book: UncheckedAccount<'info>,
+
+    // Books in the current layout already have an order slab, so they can't be migrated twice
+    #[account(init,
+        payer = authority,
+        space = OrderSlab::space(order_capacity),
+        seeds = [b"order_slab", order_book.key().as_ref()],
+        bump)]
+    pub order_slab: AccountLoader<'info, OrderSlab>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_migrate_orderbook(
+    ctx: Context<MigrateOrderbook>,
+    order_capacity: u16,
+    tick_size: u64,
+    base_lot_size: u64,
+    min_order_size: u64,
+) -> Result<()> {
+    let order_book_info = ctx.accounts.order_book.to_account_info();
+    let legacy = LegacyOrderbook::from_account_data(&order_book_info.try_borrow_data()?)?;
+
+    // Ensure only the orderbook authority can migrate the orderbook
+    require!(
+        legacy.authority == ctx.accounts.authority.key(),
+        ErrorCode::Unauthorized
+    );
+
+    let order_slab = ctx.accounts.order_slab.to_account_info();
+    let mut order_slab_data = order_slab.try_borrow_mut_data()?;
+    let mut book = Book::load(&mut order_slab_data)?;
+    book.initialize(order_book_info.key(), ctx.bumps.order_slab);
+    let migrated = legacy.buys.len() + legacy.sells.len();
+    let order_book = legacy.migrate(&mut book, order_capacity, tick_size, base_lot_size, min_order_size)?;
+
+    // Size the account for the current layout, topping up or refunding its rent
+    let space = 8 + Orderbook::INIT_SPACE;
+    let rent = Rent::get()?.minimum_balance(space);
+    let lamports = order_book_info.lamports();
+    if rent > lamports {
+        transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: order_book_info.clone(),
+                },
+            ),
+            rent - lamports,
+        )?;
+    } else {
+        **order_book_info.try_borrow_mut_lamports()? = rent;
+        **ctx.accounts.authority.try_borrow_mut_lamports()? += lamports - rent;
+    }
+    order_book_info.realloc(space, false)?;
+
+    let mut data = order_book_info.try_borrow_mut_data()?;
+    data.fill(0);
+    order_book.try_serialize(&mut &mut data[..])?;
+
+    msg!("Migrated {} orders into order slab {} with capacity {}", migrated, order_slab.key(), order_capacity);
+    msg!("Tick size: {}, base lot size: {}, min order size: {}", tick_size, base_lot_size, min_order_size);
+    Ok(())
+}
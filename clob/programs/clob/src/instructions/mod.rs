@@ -1,15 +1,54 @@
 pub mod initialize_orderbook;
 pub use initialize_orderbook::*;
 
+pub mod resize_orderbook;
+pub use resize_orderbook::*;
+
+pub mod migrate_orderbook;
+pub use migrate_orderbook::*;
+
+pub mod set_fees;
+pub use set_fees::*;
+
+pub mod set_fee_tiers;
+pub use set_fee_tiers::*;
+
+pub mod sweep_fees;
+pub use sweep_fees::*;
+
+pub mod create_event_queue;
+pub use create_event_queue::*;
+
+pub mod create_trade_history;
+pub use create_trade_history::*;
+
+pub mod create_candles;
+pub use create_candles::*;
+
+pub mod create_open_orders;
+pub use create_open_orders::*;
+
 pub mod create_order;
 pub use create_order::*;
 
+pub mod create_market_order;
+pub use create_market_order::*;
+
 pub mod shared;
 pub use shared::*;
 
 pub mod match_order;
 pub use match_order::*;
 
+pub mod consume_events;
+pub use consume_events::*;
+
+pub mod cancel_order;
+pub use cancel_order::*;
+
+pub mod cancel_all_orders;
+pub use cancel_all_orders::*;
+
 pub mod withdraw_funds;
 pub use withdraw_funds::*;
 
@@ -22,5 +61,17 @@ pub use delegate::*;
 pub mod undelegate;
 pub use undelegate::*;
 
+pub mod delegate_open_orders;
+pub use delegate_open_orders::*;
+
+pub mod undelegate_open_orders;
+pub use undelegate_open_orders::*;
+
+pub mod audit;
+pub use audit::*;
+
+pub mod get_market_stats;
+pub use get_market_stats::*;
+
 pub mod update_delegation_status;
 pub use update_delegation_status::*;
@@ -0,0 +1,76 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::token_interface::Mint;
+
+use crate::state::{Book, Orderbook, OrderSlab};
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+pub struct ResizeOrderbook<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut,
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+
+    #[account(mut,
+        seeds = [b"order_slab", order_book.key().as_ref()],
+        bump = order_slab.load()?.bump
+    )]
+    pub order_slab: AccountLoader<'info, OrderSlab>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// The runtime limits how much an account can grow in one instruction, so a large
+// increase in capacity has to be spread over several resizes
+pub fn handle_resize_orderbook(ctx: Context<ResizeOrderbook>, order_capacity: u16) -> Result<()> {
+    // Ensure only the orderbook authority can resize the orderbook
+    require!(
+        ctx.accounts.order_book.authority == ctx.accounts.authority.key(),
+        ErrorCode::Unauthorized
+    );
+    require!(order_capacity > 0, ErrorCode::InvalidOrderCapacity);
+
+    let order_slab = ctx.accounts.order_slab.to_account_info();
+    let authority = ctx.accounts.authority.to_account_info();
+    let old_capacity = OrderSlab::capacity_for_len(order_slab.data_len());
+    let new_capacity = order_capacity as usize;
+    let new_len = OrderSlab::space(order_capacity);
+    let rent_exempt_lamports = Rent::get()?.minimum_balance(new_len);
+
+    if new_capacity > old_capacity {
+        // Top the slab up to the rent exempt minimum for its new size before growing it
+        let lamports_needed = rent_exempt_lamports.saturating_sub(order_slab.lamports());
+        if lamports_needed > 0 {
+            transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    Transfer { from: authority, to: order_slab.clone() },
+                ),
+                lamports_needed,
+            )?;
+        }
+        order_slab.realloc(new_len, false)?;
+        Book::relayout(&mut order_slab.try_borrow_mut_data()?, old_capacity, new_capacity)?;
+    } else {
+        // Pack the orders into the smaller layout, then hand the spare rent back to the authority
+        Book::relayout(&mut order_slab.try_borrow_mut_data()?, old_capacity, new_capacity)?;
+        order_slab.realloc(new_len, false)?;
+
+        let excess_lamports = order_slab.lamports().saturating_sub(rent_exempt_lamports);
+        **order_slab.try_borrow_mut_lamports()? -= excess_lamports;
+        **authority.try_borrow_mut_lamports()? += excess_lamports;
+    }
+
+    let order_book = &mut ctx.accounts.order_book;
+    msg!("Resizing orderbook from {} to {} orders per side", order_book.order_capacity, order_capacity);
+    order_book.order_capacity = order_capacity;
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::state::{FeeTier, Orderbook};
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+pub struct SetFeeTiers<'info> {
+    pub authority: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut,
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+}
+
+// Replaces the whole tier table. Tiers are sorted by min_volume and a user gets the
+// rates of the highest tier their quote volume over the last FEE_VOLUME_DAYS days reaches,
+// counted in whole days; users below every tier pay the rates set with set_fees. Passing
+// no tiers removes them.
+pub fn handle_set_fee_tiers(ctx: Context<SetFeeTiers>, fee_tiers: Vec<FeeTier>) -> Result<()> {
+    // Ensure only the orderbook authority can change the fees
+    require!(
+        ctx.accounts.order_book.authority == ctx.accounts.authority.key(),
+        ErrorCode::Unauthorized
+    );
+
+    let order_book = &mut ctx.accounts.order_book;
+    order_book.fee_tiers = fee_tiers;
+    order_book.validate_fees()?;
+
+    for tier in &order_book.fee_tiers {
+        msg!("Fee tier from volume {}: maker {} bps, taker {} bps",
+            tier.min_volume, tier.maker_fee_bps, tier.taker_fee_bps);
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::state::Orderbook;
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+pub struct SetFees<'info> {
+    pub authority: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut,
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+}
+
+// Sets the rates of users below every fee tier, where a negative maker fee is a rebate,
+// and the share of taker fees paid to referrers.
+// New rates apply to fills from now on. Resting buy orders keep the maker fee they
+// reserved for when they were placed.
+pub fn handle_set_fees(
+    ctx: Context<SetFees>,
+    maker_fee_bps: i16,
+    taker_fee_bps: u16,
+    referral_share_bps: u16,
+) -> Result<()> {
+    // Ensure only the orderbook authority can change the fees
+    require!(
+        ctx.accounts.order_book.authority == ctx.accounts.authority.key(),
+        ErrorCode::Unauthorized
+    );
+
+    let order_book = &mut ctx.accounts.order_book;
+    order_book.maker_fee_bps = maker_fee_bps;
+    order_book.taker_fee_bps = taker_fee_bps;
+    order_book.referral_share_bps = referral_share_bps;
+    order_book.validate_fees()?;
+
+    msg!("Set maker fee to {} bps, taker fee to {} bps and referral share to {} bps",
+        maker_fee_bps, taker_fee_bps, referral_share_bps);
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::state::Orderbook;
+use crate::instructions::shared::transfer_tokens;
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+pub struct SweepFees<'info> {
+    pub authority: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut,
+        seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()],
+        bump = order_book.bump
+    )]
+    pub order_book: Account<'info, Orderbook>,
+
+    #[account(mut,
+        associated_token::mint = quote_token_mint,
+        associated_token::authority = order_book,
+        associated_token::token_program = token_program,
+    )]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    // Any quote token account chosen by the authority
+    #[account(mut,
+        token::mint = quote_token_mint,
+        token::token_program = token_program,
+    )]
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn handle_sweep_fees(ctx: Context<SweepFees>) -> Result<()> {
+    // Ensure only the orderbook authority can sweep the fees
+    require!(
+        ctx.accounts.order_book.authority == ctx.accounts.authority.key(),
+        ErrorCode::Unauthorized
+    );
+
+    let order_book = &mut ctx.accounts.order_book;
+    let fee_amount = order_book.accrued_fees;
+    if fee_amount == 0 {
+        msg!("No fees to sweep");
+        return Ok(());
+    }
+
+    // The vault is owned by the orderbook PDA, which signs the transfer
+    let base_mint_key = ctx.accounts.base_token_mint.key();
+    let quote_mint_key = ctx.accounts.quote_token_mint.key();
+    let account_seeds = [
+        b"orderbook",
+        base_mint_key.as_ref(),
+        quote_mint_key.as_ref(),
+        &[order_book.bump],
+    ];
+
+    transfer_tokens(
+        &ctx.accounts.quote_vault,
+        &ctx.accounts.treasury,
+        &fee_amount,
+        &ctx.accounts.quote_token_mint,
+        &order_book.to_account_info(),
+        &ctx.accounts.token_program,
+        Some(&account_seeds[..]),
+    )?;
+    order_book.accrued_fees = 0;
+
+    msg!("Swept {} quote in fees to {}", fee_amount, ctx.accounts.treasury.key());
+    Ok(())
+}
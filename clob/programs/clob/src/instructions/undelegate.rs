@@ -4,7 +4,8 @@ use anchor_spl::token_interface::Mint;
 use ephemeral_rollups_sdk::anchor::commit;
 use ephemeral_rollups_sdk::ephem::commit_and_undelegate_accounts;
 
-use crate::state::Orderbook;
+use crate::events::Undelegated;
+use crate::state::{Candles, EventQueue, Orderbook, OrderSlab, TradeHistory};
 
 #[commit]
 #[derive(Accounts)]
@@ -17,14 +18,37 @@ pub struct UndelegateOrderbook<'info> {
 
     #[account(mut, seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()], bump)]
     pub order_book: Account<'info, Orderbook>,
+
+    #[account(mut, seeds = [b"order_slab", order_book.key().as_ref()], bump)]
+    pub order_slab: AccountLoader<'info, OrderSlab>,
+
+    #[account(mut, seeds = [b"event_queue", order_book.key().as_ref()], bump)]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+
+    #[account(mut, seeds = [b"trade_history", order_book.key().as_ref()], bump)]
+    pub trade_history: AccountLoader<'info, TradeHistory>,
+
+    #[account(mut, seeds = [b"candles", order_book.key().as_ref()], bump)]
+    pub candles: AccountLoader<'info, Candles>,
 }
 
 pub fn handle_undelegate(ctx: Context<UndelegateOrderbook>) -> Result<()> {
     commit_and_undelegate_accounts(
         &ctx.accounts.user,
-        vec![&ctx.accounts.order_book.to_account_info()],
+        vec![
+            &ctx.accounts.order_book.to_account_info(),
+            &ctx.accounts.order_slab.to_account_info(),
+            &ctx.accounts.event_queue.to_account_info(),
+            &ctx.accounts.trade_history.to_account_info(),
+            &ctx.accounts.candles.to_account_info(),
+        ],
         &ctx.accounts.magic_context,
         &ctx.accounts.magic_program,
     )?;
+    emit!(Undelegated {
+        order_book: ctx.accounts.order_book.key(),
+        open_orders: None,
+        signer: ctx.accounts.user.key(),
+    });
     Ok(())
 }
@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use ephemeral_rollups_sdk::anchor::commit;
+use ephemeral_rollups_sdk::ephem::commit_and_undelegate_accounts;
+
+use crate::events::Undelegated;
+use crate::state::OpenOrders;
+
+#[commit]
+#[derive(Accounts)]
+pub struct UndelegateOpenOrders<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub base_token_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Only used to derive the open orders address; the orderbook may already be delegated
+    #[account(seeds = [b"orderbook", base_token_mint.key().as_ref(), quote_token_mint.key().as_ref()], bump)]
+    pub order_book: UncheckedAccount<'info>,
+
+    #[account(mut, seeds = [b"open_orders", order_book.key().as_ref(), user.key().as_ref()], bump)]
+    pub open_orders: Account<'info, OpenOrders>,
+}
+
+pub fn handle_undelegate_open_orders(ctx: Context<UndelegateOpenOrders>) -> Result<()> {
+    commit_and_undelegate_accounts(
+        &ctx.accounts.user,
+        vec![&ctx.accounts.open_orders.to_account_info()],
+        &ctx.accounts.magic_context,
+        &ctx.accounts.magic_program,
+    )?;
+    emit!(Undelegated {
+        order_book: ctx.accounts.order_book.key(),
+        open_orders: Some(ctx.accounts.open_orders.key()),
+        signer: ctx.accounts.user.key(),
+    });
+    Ok(())
+}
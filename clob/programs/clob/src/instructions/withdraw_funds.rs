@@ -4,9 +4,10 @@ use anchor_spl::{
     token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
-use crate::state::Orderbook;
+use crate::state::{OpenOrders, Orderbook};
 use crate::instructions::shared::transfer_tokens;
 use crate::error::ErrorCode;
+use crate::events::Withdraw;
 
 #[derive(Accounts)]
 pub struct WithdrawFundsAccountConstraints<'info> {
@@ -37,6 +38,12 @@ pub struct WithdrawFundsAccountConstraints<'info> {
         bump = order_book.bump
     )]
     pub order_book: Account<'info, Orderbook>,
+
+    #[account(mut,
+        seeds = [b"open_orders", order_book.key().as_ref(), user.key().as_ref()],
+        bump = open_orders.bump
+    )]
+    pub open_orders: Account<'info, OpenOrders>,
     
     #[account(mut,
         associated_token::mint = base_token_mint,
@@ -63,16 +70,15 @@ pub fn handle_withdraw_funds(
     quote_amount: u64,
 ) -> Result<()> {
     let order_book = &mut context.accounts.order_book;
-    let user = &context.accounts.user;
+    let open_orders = &mut context.accounts.open_orders;
+    let balances = &open_orders.balances;
     
     // Check if the user has sufficient balance
-    let (user_base_balance, user_quote_balance) = order_book.get_balance(&user.key());
-    
-    msg!("user_base_balance: {}", user_base_balance);
-    msg!("user_quote_balance: {}", user_quote_balance);
+    msg!("user_base_balance: {}", balances.base_free);
+    msg!("user_quote_balance: {}", balances.quote_free);
     
     require!(
-        user_base_balance >= base_amount && user_quote_balance >= quote_amount,
+        balances.base_free >= base_amount && balances.quote_free >= quote_amount,
         ErrorCode::InsufficientBalance
     );
     
@@ -114,7 +120,21 @@ pub fn handle_withdraw_funds(
     }
     
     // Update the user's balance
-    order_book.subtract_balance(&user.key(), base_amount, quote_amount)?;
+    open_orders.withdraw(order_book, base_amount, quote_amount)?;
+
+    context.accounts.base_vault.reload()?;
+    context.accounts.quote_vault.reload()?;
+    order_book.check_vault_balances(
+        context.accounts.base_vault.amount,
+        context.accounts.quote_vault.amount,
+    )?;
+
+    emit!(Withdraw {
+        order_book: order_book.key(),
+        owner: context.accounts.user.key(),
+        base_amount,
+        quote_amount,
+    });
     
     Ok(())
 } 
\ No newline at end of file
@@ -1,11 +1,14 @@
 pub mod constants;
 pub mod error;
+pub mod events;
 pub mod instructions;
+pub mod math;
 pub mod state;
 
 use anchor_lang::prelude::*;
 
 pub use constants::*;
+pub use events::*;
 pub use instructions::*;
 pub use state::*;
 
@@ -19,22 +22,100 @@ declare_id!("EBRUiZStSVz98fpJ9PsG3BTFBtETEXWsw9bPwsaUrkJF");
 pub mod clob {
     use super::*;
 
-    pub fn initialize_orderbook(ctx: Context<InitializeOrderbook>) -> Result<()> {
-        initialize_orderbook::init_orderbook(ctx)
+    pub fn initialize_orderbook(
+        ctx: Context<InitializeOrderbook>,
+        order_capacity: u16,
+        tick_size: u64,
+        base_lot_size: u64,
+        min_order_size: u64,
+    ) -> Result<()> {
+        initialize_orderbook::init_orderbook(ctx, order_capacity, tick_size, base_lot_size, min_order_size)
+    }
+
+    pub fn resize_orderbook(ctx: Context<ResizeOrderbook>, order_capacity: u16) -> Result<()> {
+        resize_orderbook::handle_resize_orderbook(ctx, order_capacity)
+    }
+
+    pub fn migrate_orderbook(
+        ctx: Context<MigrateOrderbook>,
+        order_capacity: u16,
+        tick_size: u64,
+        base_lot_size: u64,
+        min_order_size: u64,
+    ) -> Result<()> {
+        migrate_orderbook::handle_migrate_orderbook(ctx, order_capacity, tick_size, base_lot_size, min_order_size)
+    }
+
+    pub fn set_fees(ctx: Context<SetFees>, maker_fee_bps: i16, taker_fee_bps: u16, referral_share_bps: u16) -> Result<()> {
+        set_fees::handle_set_fees(ctx, maker_fee_bps, taker_fee_bps, referral_share_bps)
+    }
+
+    pub fn set_fee_tiers(ctx: Context<SetFeeTiers>, fee_tiers: Vec<FeeTier>) -> Result<()> {
+        set_fee_tiers::handle_set_fee_tiers(ctx, fee_tiers)
+    }
+
+    pub fn sweep_fees(ctx: Context<SweepFees>) -> Result<()> {
+        sweep_fees::handle_sweep_fees(ctx)
+    }
+
+    pub fn create_open_orders(ctx: Context<CreateOpenOrders>) -> Result<()> {
+        create_open_orders::handle_create_open_orders(ctx)
     }
 
     pub fn deposit_balance(ctx: Context<DepositBalance>, quote_amount: u64, base_amount: u64) -> Result<()> {
         deposit_balance::handle_deposit_balance(ctx, quote_amount, base_amount)
     }
 
-    pub fn create_order(ctx: Context<CreateOrder>, side: u8, price: u64, amount: u64) -> Result<()> {
-        create_order::handle_create_order(ctx, side, price, amount)
+    pub fn create_order(
+        ctx: Context<CreateOrder>,
+        side: u8,
+        time_in_force: u8,
+        self_trade_prevention: u8,
+        price: u64,
+        amount: u64,
+    ) -> Result<MatchingResult> {
+        create_order::handle_create_order(ctx, side, time_in_force, self_trade_prevention, price, amount)
+    }
+
+    pub fn create_market_order(
+        ctx: Context<CreateMarketOrder>,
+        side: u8,
+        self_trade_prevention: u8,
+        amount: u64,
+        worst_price: u64,
+        quote_budget: u64,
+    ) -> Result<MarketOrderResult> {
+        create_market_order::handle_create_market_order(ctx, side, self_trade_prevention, amount, worst_price, quote_budget)
     }
 
     pub fn match_order(ctx: Context<MatchOrder>, order_id: u64) -> Result<()> {
         match_order::handle_match_order(ctx, order_id)
     }
 
+    pub fn create_event_queue(ctx: Context<CreateEventQueue>) -> Result<()> {
+        create_event_queue::handle_create_event_queue(ctx)
+    }
+
+    pub fn create_trade_history(ctx: Context<CreateTradeHistory>) -> Result<()> {
+        create_trade_history::handle_create_trade_history(ctx)
+    }
+
+    pub fn create_candles(ctx: Context<CreateCandles>) -> Result<()> {
+        create_candles::handle_create_candles(ctx)
+    }
+
+    pub fn consume_events<'info>(ctx: Context<'_, '_, 'info, 'info, ConsumeEvents<'info>>, limit: u16) -> Result<()> {
+        consume_events::handle_consume_events(ctx, limit)
+    }
+
+    pub fn cancel_order(ctx: Context<CancelOrder>, order_id: u64) -> Result<()> {
+        cancel_order::handle_cancel_order(ctx, order_id)
+    }
+
+    pub fn cancel_all_orders(ctx: Context<CancelAllOrders>, side: Option<Side>) -> Result<CancelAllResult> {
+        cancel_all_orders::handle_cancel_all_orders(ctx, side)
+    }
+
     pub fn withdraw_funds(ctx: Context<WithdrawFundsAccountConstraints>, base_amount: u64, quote_amount: u64) -> Result<()> {
         withdraw_funds::handle_withdraw_funds(ctx, base_amount, quote_amount)
     }
@@ -47,6 +128,22 @@ pub mod clob {
         undelegate::handle_undelegate(ctx)
     }
 
+    pub fn delegate_open_orders(ctx: Context<DelegateOpenOrders>) -> Result<()> {
+        delegate_open_orders::handle_delegate_open_orders(ctx)
+    }
+
+    pub fn undelegate_open_orders(ctx: Context<UndelegateOpenOrders>) -> Result<()> {
+        undelegate_open_orders::handle_undelegate_open_orders(ctx)
+    }
+
+    pub fn audit(ctx: Context<Audit>) -> Result<()> {
+        audit::handle_audit(ctx)
+    }
+
+    pub fn get_market_stats(ctx: Context<GetMarketStats>) -> Result<MarketStats> {
+        get_market_stats::handle_get_market_stats(ctx)
+    }
+
     pub fn update_delegation_status(ctx: Context<UpdateDelegationStatus>, is_delegated: bool) -> Result<()> {
         update_delegation_status::handle_update_delegation_status(ctx, is_delegated)
     }
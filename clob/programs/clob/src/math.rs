@@ -0,0 +1,84 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::FEE_BPS_DENOMINATOR;
+use crate::error::ErrorCode;
+
+// Direction to round a division that doesn't come out even. Whatever the taker pays or a
+// maker is charged rounds up, and whatever is paid out of collected fees rounds down, so
+// rounding never leaves the vault short.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+// value * numerator / denominator, with the product held in a u128 so only a result that
+// doesn't fit in a u64 fails
+pub fn mul_div(value: u64, numerator: u64, denominator: u64, rounding: Rounding) -> Result<u64> {
+    require!(denominator > 0, ErrorCode::CalculationFailure);
+    let product = value as u128 * numerator as u128;
+    let denominator = denominator as u128;
+    let mut result = product / denominator;
+    if rounding == Rounding::Up && !product.is_multiple_of(denominator) {
+        result += 1;
+    }
+    u64::try_from(result).map_err(|_| error!(ErrorCode::CalculationFailure))
+}
+
+// Quote for `amount` base atoms at `price`, which is in quote atoms per base lot. Order
+// amounts are whole lots, so this only rounds for books priced per atom before lots existed.
+pub fn quote_amount(price: u64, amount: u64, base_lot_size: u64) -> Result<u64> {
+    mul_div(amount, price, base_lot_size, Rounding::Up)
+}
+
+// Fee on a quote amount at a rate in basis points
+pub fn fee_amount(quote_amount: u64, fee_bps: u16, rounding: Rounding) -> Result<u64> {
+    mul_div(quote_amount, fee_bps as u64, FEE_BPS_DENOMINATOR, rounding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mul_div_rounds_as_asked() {
+        assert_eq!(mul_div(10, 1, 3, Rounding::Down).unwrap(), 3);
+        assert_eq!(mul_div(10, 1, 3, Rounding::Up).unwrap(), 4);
+        // An even division is the same either way
+        assert_eq!(mul_div(12, 1, 3, Rounding::Down).unwrap(), 4);
+        assert_eq!(mul_div(12, 1, 3, Rounding::Up).unwrap(), 4);
+        assert_eq!(mul_div(0, 7, 3, Rounding::Up).unwrap(), 0);
+    }
+
+    #[test]
+    fn mul_div_fails_instead_of_overflowing() {
+        // The product only has to fit in a u128, the result in a u64
+        assert_eq!(mul_div(u64::MAX, u64::MAX, u64::MAX, Rounding::Up).unwrap(), u64::MAX);
+        assert_eq!(mul_div(u64::MAX, 2, 1, Rounding::Down).err().unwrap(), ErrorCode::CalculationFailure.into());
+        assert_eq!(mul_div(u64::MAX, u64::MAX, 1, Rounding::Up).err().unwrap(), ErrorCode::CalculationFailure.into());
+        // Rounding up the largest result doesn't overflow
+        assert_eq!(mul_div(u64::MAX, 2, 2, Rounding::Up).unwrap(), u64::MAX);
+        assert_eq!(mul_div(1, 1, 0, Rounding::Down).err().unwrap(), ErrorCode::CalculationFailure.into());
+    }
+
+    #[test]
+    fn quote_amount_rounds_up() {
+        // 3 base atoms at 10 quote per lot of 4 atoms is 7.5 quote atoms
+        assert_eq!(quote_amount(10, 3, 4).unwrap(), 8);
+        assert_eq!(quote_amount(10, 8, 4).unwrap(), 20);
+        assert_eq!(quote_amount(10, 5, 1).unwrap(), 50);
+    }
+
+    #[test]
+    fn fees_charged_round_up_and_fees_paid_out_round_down() {
+        // 5 bps of 1_001 quote atoms is 0.5005
+        let taker_fee = fee_amount(1_001, 5, Rounding::Up).unwrap();
+        let maker_rebate = fee_amount(1_001, 5, Rounding::Down).unwrap();
+        assert_eq!((taker_fee, maker_rebate), (1, 0));
+        // A 30% referral share of a 7 atom fee is 2.1 atoms
+        assert_eq!(fee_amount(7, 3_000, Rounding::Down).unwrap(), 2);
+        assert_eq!(fee_amount(7, 3_000, Rounding::Up).unwrap(), 3);
+        assert_eq!(fee_amount(20_000, 5, Rounding::Up).unwrap(), 10);
+        assert_eq!(fee_amount(20_000, 5, Rounding::Down).unwrap(), 10);
+    }
+}
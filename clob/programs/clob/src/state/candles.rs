@@ -0,0 +1,225 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::CANDLE_COUNT;
+
+// Open, high, low and close prices and traded volume of one interval, starting at
+// start_time. Intervals without trades have no candle.
+#[zero_copy]
+pub struct Candle {
+    pub start_time: i64,
+    pub open: u64,
+    pub high: u64,
+    pub low: u64,
+    pub close: u64,
+    pub base_volume: u64,
+    pub quote_volume: u64,
+}
+
+// The latest candles of one interval length, oldest overwritten first
+#[zero_copy]
+pub struct CandleSeries {
+    // Length of each candle in seconds
+    pub interval: i64,
+    // Slot of the latest candle
+    pub head: u32,
+    pub count: u32,
+    pub candles: [Candle; CANDLE_COUNT],
+}
+
+// Price history of an orderbook in 1 minute, 1 hour and 1 day candles, updated on every
+// fill so charts and risk checks can read it from chain state
+#[account(zero_copy)]
+pub struct Candles {
+    pub order_book: Pubkey,
+    pub bump: u8,
+    pub padding: [u8; 7],
+    pub minutes: CandleSeries,
+    pub hours: CandleSeries,
+    pub days: CandleSeries,
+}
+
+impl CandleSeries {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * 60;
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn initialize(&mut self, interval: i64) {
+        self.interval = interval;
+        self.head = 0;
+        self.count = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn latest(&self) -> Option<&Candle> {
+        (!self.is_empty()).then(|| &self.candles[self.head as usize])
+    }
+
+    // Candles from the latest back
+    pub fn recent(&self) -> impl Iterator<Item = &Candle> {
+        (0..self.len()).map(move |age| &self.candles[(self.head as usize + CANDLE_COUNT - age) % CANDLE_COUNT])
+    }
+
+    // Volume as (base, quote) of the candles starting at or after start_time
+    pub fn volume_since(&self, start_time: i64) -> (u64, u64) {
+        self.recent()
+            .take_while(|candle| candle.start_time >= start_time)
+            .fold((0, 0), |(base, quote), candle| {
+                (base.saturating_add(candle.base_volume), quote.saturating_add(candle.quote_volume))
+            })
+    }
+
+    // Add a fill to the candle of the interval containing `now`, opening a new candle when
+    // the interval has moved on. A timestamp behind the latest candle counts towards it.
+    pub fn record(&mut self, price: u64, base_amount: u64, quote_amount: u64, now: i64) {
+        let start_time = now - now.rem_euclid(self.interval);
+        match self.latest().map(|latest| latest.start_time) {
+            Some(latest_start) if start_time <= latest_start => {
+                let candle = &mut self.candles[self.head as usize];
+                candle.high = candle.high.max(price);
+                candle.low = candle.low.min(price);
+                candle.close = price;
+                candle.base_volume = candle.base_volume.saturating_add(base_amount);
+                candle.quote_volume = candle.quote_volume.saturating_add(quote_amount);
+            },
+            _ => {
+                if !self.is_empty() {
+                    self.head = ((self.head as usize + 1) % CANDLE_COUNT) as u32;
+                }
+                self.count = (self.count + 1).min(CANDLE_COUNT as u32);
+                self.candles[self.head as usize] = Candle {
+                    start_time,
+                    open: price,
+                    high: price,
+                    low: price,
+                    close: price,
+                    base_volume: base_amount,
+                    quote_volume: quote_amount,
+                };
+            },
+        }
+    }
+}
+
+impl Candles {
+    pub fn initialize(&mut self, order_book: Pubkey, bump: u8) {
+        self.order_book = order_book;
+        self.bump = bump;
+        self.minutes.initialize(CandleSeries::MINUTE);
+        self.hours.initialize(CandleSeries::HOUR);
+        self.days.initialize(CandleSeries::DAY);
+    }
+
+    // Volume as (base, quote) over the hour containing `now` and the 23 before it
+    pub fn volume_24h(&self, now: i64) -> (u64, u64) {
+        let current_hour = now - now.rem_euclid(CandleSeries::HOUR);
+        self.hours.volume_since(current_hour - 23 * CandleSeries::HOUR)
+    }
+
+    pub fn record(&mut self, price: u64, base_amount: u64, quote_amount: u64, now: i64) {
+        for series in [&mut self.minutes, &mut self.hours, &mut self.days] {
+            series.record(price, base_amount, quote_amount, now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: i64 = CandleSeries::MINUTE;
+    const HOUR: i64 = CandleSeries::HOUR;
+    const DAY: i64 = CandleSeries::DAY;
+
+    fn candles() -> Box<Candles> {
+        let mut candles: Box<Candles> = Box::new(bytemuck::Zeroable::zeroed());
+        candles.initialize(Pubkey::new_unique(), 0);
+        candles
+    }
+
+    fn ohlc(candle: &Candle) -> (i64, u64, u64, u64, u64, u64, u64) {
+        (candle.start_time, candle.open, candle.high, candle.low, candle.close, candle.base_volume, candle.quote_volume)
+    }
+
+    #[test]
+    fn fills_in_one_interval_update_its_candle() {
+        let mut candles = candles();
+        candles.record(10, 1, 10, 5);
+        candles.record(14, 2, 28, 20);
+        candles.record(8, 1, 8, 40);
+        candles.record(11, 3, 33, 59);
+
+        for series in [&candles.minutes, &candles.hours, &candles.days] {
+            assert_eq!(series.len(), 1);
+            assert_eq!(ohlc(series.latest().unwrap()), (0, 10, 14, 8, 11, 7, 79));
+        }
+    }
+
+    #[test]
+    fn each_interval_rolls_over_on_its_own_boundary() {
+        let mut candles = candles();
+        candles.record(10, 1, 10, 0);
+        // A new minute, in the same hour and day
+        candles.record(12, 1, 12, MINUTE);
+        // A new hour, in the same day
+        candles.record(9, 1, 9, HOUR + 30);
+        // A new day
+        candles.record(11, 2, 22, DAY + HOUR);
+
+        let starts = |series: &CandleSeries| series.recent().map(|candle| candle.start_time).collect::<Vec<i64>>();
+        assert_eq!(starts(&candles.minutes), vec![DAY + HOUR, HOUR, MINUTE, 0]);
+        assert_eq!(starts(&candles.hours), vec![DAY + HOUR, HOUR, 0]);
+        assert_eq!(starts(&candles.days), vec![DAY, 0]);
+
+        assert_eq!(ohlc(candles.hours.recent().nth(2).unwrap()), (0, 10, 12, 10, 12, 2, 22));
+        assert_eq!(ohlc(candles.days.recent().nth(1).unwrap()), (0, 10, 12, 9, 9, 3, 31));
+        // A new candle opens at the price of its first fill
+        assert_eq!(ohlc(candles.days.latest().unwrap()), (DAY, 11, 11, 11, 11, 2, 22));
+    }
+
+    #[test]
+    fn late_fills_count_towards_the_latest_candle() {
+        let mut candles = candles();
+        candles.record(10, 1, 10, 2 * MINUTE);
+        candles.record(12, 1, 12, MINUTE);
+        assert_eq!(candles.minutes.len(), 1);
+        assert_eq!(ohlc(candles.minutes.latest().unwrap()), (2 * MINUTE, 10, 12, 10, 12, 2, 22));
+    }
+
+    #[test]
+    fn overwrites_the_oldest_candles_once_full() {
+        let mut candles = candles();
+        let minutes = CANDLE_COUNT as i64 + 3;
+        for minute in 0..minutes {
+            candles.record(minute as u64 + 1, 1, 1, minute * MINUTE);
+        }
+        assert_eq!(candles.minutes.len(), CANDLE_COUNT);
+        assert_eq!(candles.minutes.head as usize, 2);
+
+        let starts: Vec<i64> = candles.minutes.recent().map(|candle| candle.start_time / MINUTE).collect();
+        let expected: Vec<i64> = (3..minutes).rev().collect();
+        assert_eq!(starts, expected);
+        assert_eq!(candles.minutes.latest().unwrap().open, minutes as u64);
+    }
+
+    #[test]
+    fn volume_24h_drops_hours_older_than_a_day() {
+        let mut candles = candles();
+        candles.record(10, 1, 10, 0);
+        candles.record(10, 2, 20, 10 * HOUR);
+        candles.record(10, 4, 40, 23 * HOUR + 59 * MINUTE);
+
+        // The hour containing `now` and the 23 before it
+        assert_eq!(candles.volume_24h(23 * HOUR + 59 * MINUTE), (7, 70));
+        assert_eq!(candles.volume_24h(24 * HOUR), (6, 60));
+        assert_eq!(candles.volume_24h(34 * HOUR - 1), (6, 60));
+        assert_eq!(candles.volume_24h(34 * HOUR), (4, 40));
+        assert_eq!(candles.volume_24h(47 * HOUR), (0, 0));
+    }
+}
@@ -0,0 +1,164 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::EVENT_QUEUE_CAPACITY;
+use crate::error::ErrorCode;
+use crate::state::OpenOrders;
+
+// What an event does to the maker's open orders account
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EventKind {
+    // Part of a resting order was filled: settle the maker's side of the fill
+    Fill = 0,
+    // A resting order left the book after its last fill: stop tracking it
+    Out = 1,
+}
+
+// The maker's side of a fill or of an order leaving the book. The orderbook's totals
+// already include it; only the maker's own balances wait for the event to be consumed.
+#[zero_copy]
+pub struct MakerEvent {
+    pub maker: Pubkey,
+    pub order_id: u64,
+    // Funds the maker's order stops holding
+    pub base_released: u64,
+    pub quote_released: u64,
+    // Proceeds, and any unused fee reserve or rebate, for the maker's free balance
+    pub base_credit: u64,
+    pub quote_credit: u64,
+    // Quote traded, counted towards the maker's fee tier
+    pub quote_volume: u64,
+    pub timestamp: i64,
+    pub kind: u8,
+    pub padding: [u8; 7],
+}
+
+// Events that matching leaves for makers, so that a match never has to load the makers'
+// accounts. Matching pushes to the back and consume_events settles from the front.
+#[account(zero_copy)]
+pub struct EventQueue {
+    pub order_book: Pubkey,
+    pub head: u32,
+    pub count: u32,
+    // Number of events ever pushed
+    pub seq_num: u64,
+    pub bump: u8,
+    pub padding: [u8; 7],
+    pub events: [MakerEvent; EVENT_QUEUE_CAPACITY],
+}
+
+impl MakerEvent {
+    pub fn fill(maker: Pubkey, order_id: u64, released: (u64, u64), credit: (u64, u64), quote_volume: u64, timestamp: i64) -> Self {
+        MakerEvent {
+            maker,
+            order_id,
+            base_released: released.0,
+            quote_released: released.1,
+            base_credit: credit.0,
+            quote_credit: credit.1,
+            quote_volume,
+            timestamp,
+            kind: EventKind::Fill as u8,
+            padding: [0; 7],
+        }
+    }
+
+    pub fn out(maker: Pubkey, order_id: u64, timestamp: i64) -> Self {
+        MakerEvent {
+            maker,
+            order_id,
+            base_released: 0,
+            quote_released: 0,
+            base_credit: 0,
+            quote_credit: 0,
+            quote_volume: 0,
+            timestamp,
+            kind: EventKind::Out as u8,
+            padding: [0; 7],
+        }
+    }
+
+    pub fn kind(&self) -> EventKind {
+        match self.kind {
+            0 => EventKind::Fill,
+            _ => EventKind::Out,
+        }
+    }
+
+    // Apply the event to the maker's open orders account
+    pub fn apply(&self, open_orders: &mut OpenOrders) -> Result<()> {
+        match self.kind() {
+            EventKind::Fill => {
+                open_orders.balances.spend_locked(self.base_released, self.quote_released)?;
+                open_orders.balances.credit(self.base_credit, self.quote_credit)?;
+                open_orders.record_volume(self.quote_volume, self.timestamp);
+            },
+            EventKind::Out => open_orders.remove_order_id(self.order_id),
+        }
+        Ok(())
+    }
+}
+
+impl EventQueue {
+    pub fn initialize(&mut self, order_book: Pubkey, bump: u8) {
+        self.order_book = order_book;
+        self.head = 0;
+        self.count = 0;
+        self.seq_num = 0;
+        self.bump = bump;
+    }
+
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    // Events that haven't been consumed yet, oldest first
+    pub fn pending(&self) -> impl Iterator<Item = &MakerEvent> {
+        (0..self.len()).map(move |age| &self.events[(self.head as usize + age) % EVENT_QUEUE_CAPACITY])
+    }
+
+    pub fn push(&mut self, event: MakerEvent) -> Result<()> {
+        require!(self.len() < EVENT_QUEUE_CAPACITY, ErrorCode::EventQueueFull);
+        let slot = (self.head as usize + self.len()) % EVENT_QUEUE_CAPACITY;
+        self.events[slot] = event;
+        self.count += 1;
+        self.seq_num += 1;
+        Ok(())
+    }
+
+    // Oldest event that hasn't been consumed
+    pub fn front(&self) -> Option<&MakerEvent> {
+        (!self.is_empty()).then(|| &self.events[self.head as usize])
+    }
+
+    pub fn pop_front(&mut self) -> Option<MakerEvent> {
+        let event = *self.front()?;
+        self.head = ((self.head as usize + 1) % EVENT_QUEUE_CAPACITY) as u32;
+        self.count -= 1;
+        Some(event)
+    }
+
+    // Apply up to `limit` events from the front of the queue to the makers' accounts.
+    // Stops at the first event whose maker's account isn't given, so each maker's events
+    // are always applied in order. Returns the number of events consumed.
+    pub fn consume(&mut self, makers: &mut [&mut OpenOrders], limit: usize) -> Result<usize> {
+        let mut consumed = 0;
+        while consumed < limit {
+            let Some(event) = self.front().copied() else {
+                break;
+            };
+            let Some(maker) = makers.iter_mut().find(|maker| maker.owner == event.maker) else {
+                msg!("Open orders account of {} is needed to consume the next event", event.maker);
+                break;
+            };
+            event.apply(maker)?;
+            self.pop_front();
+            consumed += 1;
+        }
+        Ok(consumed)
+    }
+}
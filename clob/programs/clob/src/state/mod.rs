@@ -1,2 +1,20 @@
 pub mod orderbook;
 pub use orderbook::*;
+
+pub mod order_slab;
+pub use order_slab::*;
+
+pub mod event_queue;
+pub use event_queue::*;
+
+pub mod trade_history;
+pub use trade_history::*;
+
+pub mod candles;
+pub use candles::*;
+
+pub mod open_orders;
+pub use open_orders::*;
+
+#[cfg(test)]
+pub mod test_market;
@@ -0,0 +1,946 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::{FEE_VOLUME_DAYS, MAX_OPEN_ORDERS};
+use crate::error::ErrorCode;
+use crate::events::OrderFilled;
+use crate::math::{fee_amount, quote_amount, Rounding};
+use crate::state::{Book, Candles, EventKind, EventQueue, MakerEvent, Order, Orderbook, Side, Trade, TradeHistory};
+
+// Free balances can be withdrawn or used for new orders; locked balances are held by
+// resting orders
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct Balances {
+    pub base_free: u64,
+    pub quote_free: u64,
+    pub base_locked: u64,
+    pub quote_locked: u64,
+}
+
+impl Balances {
+    // Add to the free balances
+    pub fn credit(&mut self, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.base_free = self.base_free.checked_add(base_amount)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        self.quote_free = self.quote_free.checked_add(quote_amount)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        Ok(())
+    }
+
+    // Take from the free balances
+    pub fn debit(&mut self, base_amount: u64, quote_amount: u64) -> Result<()> {
+        require!(
+            self.base_free >= base_amount && self.quote_free >= quote_amount,
+            ErrorCode::InsufficientBalance
+        );
+        self.base_free -= base_amount;
+        self.quote_free -= quote_amount;
+        Ok(())
+    }
+
+    // Move funds from the free to the locked balances
+    pub fn lock(&mut self, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.debit(base_amount, quote_amount)?;
+        self.add_locked(base_amount, quote_amount)
+    }
+
+    // Add to the locked balances
+    pub fn add_locked(&mut self, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.base_locked = self.base_locked.checked_add(base_amount)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        self.quote_locked = self.quote_locked.checked_add(quote_amount)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        Ok(())
+    }
+
+    // Move funds from the locked back to the free balances
+    pub fn unlock(&mut self, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.spend_locked(base_amount, quote_amount)?;
+        self.credit(base_amount, quote_amount)
+    }
+
+    // Take from the locked balances, paying out a filled order
+    pub fn spend_locked(&mut self, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.base_locked = self.base_locked.checked_sub(base_amount)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        self.quote_locked = self.quote_locked.checked_sub(quote_amount)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        Ok(())
+    }
+
+    // Free plus locked, as (base, quote)
+    pub fn total(&self) -> Result<(u64, u64)> {
+        let base_amount = self.base_free.checked_add(self.base_locked)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        let quote_amount = self.quote_free.checked_add(self.quote_locked)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        Ok((base_amount, quote_amount))
+    }
+}
+
+// A user's funds on one orderbook and the IDs of the orders locking part of them
+#[account]
+#[derive(InitSpace)]
+pub struct OpenOrders {
+    pub order_book: Pubkey,
+    pub owner: Pubkey,
+    pub balances: Balances,
+    #[max_len(MAX_OPEN_ORDERS)]
+    pub order_ids: Vec<u64>,
+    pub bump: u8,
+    // Quote traded as maker or taker on each of the last FEE_VOLUME_DAYS days, which sets
+    // the fee tier. A day's volume is kept at its day number modulo FEE_VOLUME_DAYS.
+    pub daily_volume: [u64; FEE_VOLUME_DAYS],
+    // Day number, counted from the unix epoch, of the latest day volume was recorded for
+    pub volume_day: i64,
+}
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+impl OpenOrders {
+    // Record a resting order
+    pub fn track_order(&mut self, order_id: u64) -> Result<()> {
+        require!(self.order_ids.len() < MAX_OPEN_ORDERS, ErrorCode::TooManyOpenOrders);
+        self.order_ids.push(order_id);
+        Ok(())
+    }
+
+    // Add a deposit to the user's free balances and the orderbook's totals
+    pub fn deposit(&mut self, order_book: &mut Orderbook, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.balances.credit(base_amount, quote_amount)?;
+        order_book.totals.credit(base_amount, quote_amount)
+    }
+
+    // Take a withdrawal from the user's free balances and the orderbook's totals
+    pub fn withdraw(&mut self, order_book: &mut Orderbook, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.balances.debit(base_amount, quote_amount)?;
+        order_book.totals.debit(base_amount, quote_amount)
+    }
+
+    // Hold free funds for a resting order
+    pub fn lock(&mut self, order_book: &mut Orderbook, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.balances.lock(base_amount, quote_amount)?;
+        order_book.totals.lock(base_amount, quote_amount)
+    }
+
+    // Return funds held by a cancelled order to the free balances
+    pub fn unlock(&mut self, order_book: &mut Orderbook, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.balances.unlock(base_amount, quote_amount)?;
+        order_book.totals.unlock(base_amount, quote_amount)
+    }
+
+    // Take over what the owner had on the orderbook before open orders accounts existed:
+    // their legacy balance, and the funds held by their migrated orders, including what
+    // fills of those orders still waiting on the event queue release. The orders' funds
+    // joined the orderbook's totals when they were migrated.
+    pub fn claim_legacy_funds(&mut self, order_book: &mut Orderbook, book: &Book, event_queue: &EventQueue) -> Result<()> {
+        let (base_amount, quote_amount) = order_book.take_legacy_balance(&self.owner);
+        self.deposit(order_book, base_amount, quote_amount)?;
+        for order in book.orders(Side::Buy).chain(book.orders(Side::Sell)) {
+            if order.owner == self.owner {
+                let (base_amount, quote_amount) = order.locked_amounts(order_book.lot_size())?;
+                self.balances.add_locked(base_amount, quote_amount)?;
+                self.track_order(order.id)?;
+            }
+        }
+        for event in event_queue.pending() {
+            if event.maker == self.owner && event.kind() == EventKind::Fill {
+                self.balances.add_locked(event.base_released, event.quote_released)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Forget an order that no longer rests on the book
+    pub fn remove_order_id(&mut self, order_id: u64) {
+        self.order_ids.retain(|id| *id != order_id);
+    }
+
+    // Traded volume that counts towards the fee tier at `now`: that of the day containing
+    // `now` and the FEE_VOLUME_DAYS - 1 days before it
+    pub fn current_volume(&self, now: i64) -> u64 {
+        let today = now.div_euclid(SECONDS_PER_DAY);
+        let window = FEE_VOLUME_DAYS as i64;
+        // Nothing is recorded after the latest day, and days before its window were cleared
+        let first_day = (today - window + 1).max(self.volume_day - window + 1);
+        (first_day..=today.min(self.volume_day))
+            .map(|day| self.daily_volume[day.rem_euclid(window) as usize])
+            .fold(0, u64::saturating_add)
+    }
+
+    // Add a fill's quote amount to the traded volume of its day. Days skipped since the
+    // last fill are cleared as the window rolls past them, and a fill older than the
+    // window no longer counts.
+    pub fn record_volume(&mut self, quote_amount: u64, now: i64) {
+        let day = now.div_euclid(SECONDS_PER_DAY);
+        let window = FEE_VOLUME_DAYS as i64;
+        if day > self.volume_day {
+            for skipped in self.volume_day + 1..=day.min(self.volume_day + window) {
+                self.daily_volume[skipped.rem_euclid(window) as usize] = 0;
+            }
+            self.volume_day = day;
+        }
+        if self.volume_day - day < window {
+            let volume = &mut self.daily_volume[day.rem_euclid(window) as usize];
+            *volume = volume.saturating_add(quote_amount);
+        }
+    }
+}
+
+// Where the taker's side of each fill is paid from
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TakerFunds {
+    // A market order, paid from the free balance
+    Free,
+    // A limit order, paid from the funds escrowed for it when it was placed
+    Locked,
+}
+
+// Accounts that keep an orderbook's price history, written on every fill
+pub struct MarketHistory<'a> {
+    pub trades: &'a mut TradeHistory,
+    pub candles: &'a mut Candles,
+}
+
+impl MarketHistory<'_> {
+    pub fn record(&mut self, trade: Trade, quote_amount: u64) {
+        self.candles.record(trade.price, trade.amount, quote_amount, trade.timestamp);
+        self.trades.record(trade);
+    }
+}
+
+// Open orders accounts a match can settle against: the taker's and the taker's referrer's.
+// Makers' sides of each fill go to the orderbook's event queue and are settled later by
+// consume_events, so a match never loads the makers' accounts. Every balance change,
+// makers' included, is mirrored in the orderbook's totals right away, fees accrue on the
+// orderbook and each fill is recorded in its trade history and candles.
+pub struct Traders<'a> {
+    taker: &'a mut OpenOrders,
+    referrer: Option<&'a mut OpenOrders>,
+    order_book: &'a mut Orderbook,
+    event_queue: &'a mut EventQueue,
+    history: MarketHistory<'a>,
+    // The taker's rate is fixed for the whole match so a quote budget can account for it
+    taker_fee_bps: u16,
+    taker_funds: TakerFunds,
+    // Quote escrowed for a buy's taker fee beyond the maker fee reserve its order locks,
+    // drawn on before the free balance when a fill's fee isn't covered
+    escrow_surplus: u64,
+    now: i64,
+    slot: u64,
+}
+
+impl<'a> Traders<'a> {
+    pub fn load(
+        taker: &'a mut OpenOrders,
+        order_book: &'a mut Orderbook,
+        referrer: Option<&'a mut OpenOrders>,
+        event_queue: &'a mut EventQueue,
+        history: MarketHistory<'a>,
+        taker_funds: TakerFunds,
+        clock: &Clock,
+    ) -> Result<Self> {
+        if let Some(referrer) = &referrer {
+            require_keys_eq!(referrer.order_book, taker.order_book, ErrorCode::InvalidReferrer);
+            require_keys_neq!(referrer.owner, taker.owner, ErrorCode::InvalidReferrer);
+        }
+        let now = clock.unix_timestamp;
+        let (_, taker_fee_bps) = order_book.fee_rates(taker.current_volume(now));
+        Ok(Traders {
+            taker,
+            referrer,
+            order_book,
+            event_queue,
+            history,
+            taker_fee_bps,
+            taker_funds,
+            escrow_surplus: 0,
+            now,
+            slot: clock.slot,
+        })
+    }
+
+    // The open orders account of the taker or the referrer
+    pub fn get_mut(&mut self, owner: &Pubkey) -> Result<&mut OpenOrders> {
+        if self.taker.owner == *owner {
+            return Ok(&mut *self.taker);
+        }
+        self.referrer
+            .as_deref_mut()
+            .filter(|referrer| referrer.owner == *owner)
+            .ok_or(error!(ErrorCode::OpenOrdersNotFound))
+    }
+
+    // Fee rate charged to the taker on each fill
+    pub fn taker_fee_bps(&self) -> u16 {
+        self.taker_fee_bps
+    }
+
+    pub fn base_lot_size(&self) -> u64 {
+        self.order_book.lot_size()
+    }
+
+    // Address of the orderbook the traders belong to, for events
+    pub fn order_book_key(&self) -> Pubkey {
+        self.taker.order_book
+    }
+
+    // Return funds held by a resting order to its owner's free balance
+    pub fn unlock(&mut self, owner: &Pubkey, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.get_mut(owner)?.balances.unlock(base_amount, quote_amount)?;
+        self.order_book.totals.unlock(base_amount, quote_amount)
+    }
+
+    fn credit(&mut self, owner: &Pubkey, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.get_mut(owner)?.balances.credit(base_amount, quote_amount)?;
+        self.order_book.totals.credit(base_amount, quote_amount)
+    }
+
+    fn debit(&mut self, owner: &Pubkey, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.get_mut(owner)?.balances.debit(base_amount, quote_amount)?;
+        self.order_book.totals.debit(base_amount, quote_amount)
+    }
+
+    fn spend_locked(&mut self, owner: &Pubkey, base_amount: u64, quote_amount: u64) -> Result<()> {
+        self.get_mut(owner)?.balances.spend_locked(base_amount, quote_amount)?;
+        self.order_book.totals.spend_locked(base_amount, quote_amount)
+    }
+
+    // Queue the maker's side of a fill of base_amount of a resting order: the funds its
+    // order stops holding and what the maker is credited, as (base, quote). The event
+    // ends the order's tracking when the fill takes the rest of it.
+    fn queue_maker_fill(&mut self, resting: &Order, base_amount: u64, released: (u64, u64), credit: (u64, u64), quote_volume: u64) -> Result<()> {
+        self.order_book.totals.spend_locked(released.0, released.1)?;
+        self.order_book.totals.credit(credit.0, credit.1)?;
+        self.event_queue.push(MakerEvent::fill(resting.owner, resting.id, released, credit, quote_volume, self.now))?;
+        if base_amount == resting.remaining_amount {
+            self.event_queue.push(MakerEvent::out(resting.owner, resting.id, self.now))?;
+        }
+        Ok(())
+    }
+
+    // Lock what a new limit order holds while it rests and, for a buy that can take
+    // liquidity, the larger of its maker fee reserve and the taker fee on its whole
+    // notional, so an order funded for either can be matched. A buy that can't cross never
+    // pays a taker fee and locks only what it holds while resting. Fills are paid out of
+    // the escrow. Taker fees round up on each fill, so a buy filled in several parts can
+    // still take an atom or so from the free balance. Call release_escrow_surplus once
+    // matching is done.
+    pub fn escrow(&mut self, taker_order: &Order, can_take: bool) -> Result<()> {
+        let lot_size = self.order_book.lot_size();
+        let (base_locked, quote_locked) = taker_order.locked_amounts(lot_size)?;
+        if taker_order.side == Side::Buy && can_take {
+            let notional = quote_amount(taker_order.price, taker_order.remaining_amount, lot_size)?;
+            let taker_fee = fee_amount(notional, self.taker_fee_bps, Rounding::Up)?;
+            self.escrow_surplus = taker_fee.saturating_sub(quote_locked - notional);
+        }
+        let quote_locked = quote_locked.checked_add(self.escrow_surplus)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        self.taker.lock(self.order_book, base_locked, quote_locked)
+    }
+
+    // Return the part of the escrowed taker fee that the fills didn't need, leaving only
+    // what the resting part of the order holds locked
+    pub fn release_escrow_surplus(&mut self) -> Result<()> {
+        let surplus = std::mem::take(&mut self.escrow_surplus);
+        let owner = self.taker.owner;
+        self.unlock(&owner, 0, surplus)
+    }
+
+    // Return what a locked taker order held for a part that won't be filled, such as one
+    // cancelled by self-trade prevention. Call before reducing the order's remaining amount.
+    pub fn release_taker(&mut self, taker_order: &Order, amount: u64) -> Result<()> {
+        if self.taker_funds == TakerFunds::Locked {
+            let (base_amount, quote_amount) = taker_order.released_amounts(amount, self.order_book.lot_size())?;
+            self.unlock(&taker_order.owner, base_amount, quote_amount)?;
+        }
+        Ok(())
+    }
+
+    // Take the taker's side of a fill of `amount` of its order. A locked order pays out of
+    // what it locked for that part at its own price, and any surplus, such as the price
+    // improvement of a buy filled below its limit, goes back to the free balance. A fee
+    // the lock doesn't cover comes from the escrowed surplus, then from the free balance.
+    fn charge_taker(&mut self, taker_order: &Order, amount: u64, base_cost: u64, quote_cost: u64) -> Result<()> {
+        let owner = taker_order.owner;
+        if self.taker_funds == TakerFunds::Free {
+            return self.debit(&owner, base_cost, quote_cost);
+        }
+        let (base_locked, quote_locked) = taker_order.released_amounts(amount, self.order_book.lot_size())?;
+        let quote_shortfall = quote_cost.saturating_sub(quote_locked);
+        let from_surplus = quote_shortfall.min(self.escrow_surplus);
+        self.escrow_surplus -= from_surplus;
+        self.spend_locked(&owner, base_locked, quote_locked + from_surplus)?;
+        self.credit(&owner, base_locked.saturating_sub(base_cost), quote_locked.saturating_sub(quote_cost))?;
+        self.debit(&owner, base_cost.saturating_sub(base_locked), quote_shortfall - from_surplus)
+    }
+
+    // Settle a fill of base_amount between a taker order and a resting order, for
+    // quote_amount at the resting order's price. Call before reducing either order's
+    // remaining amount. The taker pays as set by TakerFunds and the maker from the funds
+    // its order locked, each is credited what the other paid, and both fees are taken in
+    // quote. The maker pays the rate its order was placed with; a maker rebate and then
+    // the referrer's share are paid out of the taker fee. Returns the fee charged to the taker.
+    pub fn settle_fill(&mut self, taker_order: &Order, resting: &Order, base_amount: u64, quote_amount: u64) -> Result<u64> {
+        let taker = &taker_order.owner;
+        let taker_fee = fee_amount(quote_amount, self.taker_fee_bps, Rounding::Up)?;
+        let maker_rebate = fee_amount(quote_amount, resting.maker_fee_bps.min(0).unsigned_abs(), Rounding::Down)?
+            .min(taker_fee);
+        let (base_released, quote_released) = resting.released_amounts(base_amount, self.order_book.lot_size())?;
+
+        let maker_fee = match resting.side {
+            // The taker buys: it pays the quote plus its fee, and the maker is paid the quote
+            // less its fee for the base its order held
+            Side::Sell => {
+                let maker_fee = fee_amount(quote_amount, resting.maker_fee_bps.max(0) as u16, Rounding::Up)?;
+                let taker_cost = quote_amount.checked_add(taker_fee)
+                    .ok_or(ErrorCode::CalculationFailure)?;
+                let maker_proceeds = (quote_amount - maker_fee).checked_add(maker_rebate)
+                    .ok_or(ErrorCode::CalculationFailure)?;
+                self.charge_taker(taker_order, base_amount, 0, taker_cost)?;
+                self.credit(taker, base_amount, 0)?;
+                self.queue_maker_fill(resting, base_amount, (base_released, 0), (0, maker_proceeds), quote_amount)?;
+                maker_fee
+            },
+            // The taker sells: it is paid the quote less its fee, and the maker's order gives
+            // up the quote along with the fee reserve it locked for this part of the order,
+            // which is the maker fee. A rebate goes back to the maker.
+            Side::Buy => {
+                let maker_fee = quote_released.checked_sub(quote_amount)
+                    .ok_or(ErrorCode::CalculationFailure)?;
+                self.charge_taker(taker_order, base_amount, base_amount, 0)?;
+                self.credit(taker, 0, quote_amount - taker_fee)?;
+                self.queue_maker_fill(resting, base_amount, (0, quote_released), (base_amount, maker_rebate), quote_amount)?;
+                maker_fee
+            },
+        };
+
+        let referral_fee = self.pay_referrer(taker_fee, taker_fee - maker_rebate)?;
+        self.order_book.accrued_fees = self.order_book.accrued_fees
+            .checked_add(taker_fee - maker_rebate - referral_fee)
+            .and_then(|fees| fees.checked_add(maker_fee))
+            .ok_or(ErrorCode::CalculationFailure)?;
+
+        // The taker's fill counts towards its fee tier now, the maker's when its event is consumed
+        let now = self.now;
+        self.taker.record_volume(quote_amount, now);
+        self.order_book.record_trade(resting.price, base_amount, quote_amount);
+
+        self.history.record(Trade {
+            maker: resting.owner,
+            taker: *taker,
+            price: resting.price,
+            amount: base_amount,
+            slot: self.slot,
+            timestamp: now,
+            taker_side: taker_order.side as u8,
+            padding: [0; 7],
+        }, quote_amount);
+
+        emit!(OrderFilled {
+            order_book: self.taker.order_book,
+            maker: resting.owner,
+            taker: *taker,
+            maker_order_id: resting.id,
+            taker_order_id: taker_order.id,
+            taker_side: taker_order.side,
+            price: resting.price,
+            amount: base_amount,
+            quote_amount,
+            taker_fee,
+            maker_fee,
+            maker_rebate,
+            timestamp: now,
+        });
+        Ok(taker_fee)
+    }
+
+    // Credit the referrer, if there is one, with their share of a taker fee, limited to what
+    // is left of the fee after any maker rebate. Returns the amount credited.
+    fn pay_referrer(&mut self, taker_fee: u64, available: u64) -> Result<u64> {
+        let Some(referrer_owner) = self.referrer.as_ref().map(|referrer| referrer.owner) else {
+            return Ok(0);
+        };
+        let referral_fee = fee_amount(taker_fee, self.order_book.referral_share_bps, Rounding::Down)?
+            .min(available);
+        self.credit(&referrer_owner, 0, referral_fee)?;
+        Ok(referral_fee)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{Candle, FeeTier, LegacyOrder, TimeInForce, UserBalance};
+    use crate::state::test_market::Market;
+    use crate::constants::EVENT_QUEUE_CAPACITY;
+
+    // The orderbook's totals are the sum of its users' balances
+    fn assert_totals(order_book: &Orderbook, users: &[&OpenOrders]) {
+        let mut sum = Balances::default();
+        for user in users {
+            sum.credit(user.balances.base_free, user.balances.quote_free).unwrap();
+            sum.add_locked(user.balances.base_locked, user.balances.quote_locked).unwrap();
+        }
+        assert_eq!(
+            (sum.base_free, sum.quote_free, sum.base_locked, sum.quote_locked),
+            (order_book.totals.base_free, order_book.totals.quote_free, order_book.totals.base_locked, order_book.totals.quote_locked)
+        );
+    }
+
+    #[test]
+    fn totals_follow_deposits_withdrawals_and_locks() {
+        let mut market = Market::new(0, 0, 2);
+        let (mut alice, mut bob) = (market.user(0), market.user(1));
+        let order_book = &mut market.order_book;
+
+        alice.deposit(order_book, 10, 1_000).unwrap();
+        bob.deposit(order_book, 5, 0).unwrap();
+        assert_totals(order_book, &[&alice, &bob]);
+
+        alice.lock(order_book, 0, 600).unwrap();
+        bob.lock(order_book, 5, 0).unwrap();
+        assert_totals(order_book, &[&alice, &bob]);
+        assert_eq!((order_book.totals.base_locked, order_book.totals.quote_locked), (5, 600));
+
+        alice.unlock(order_book, 0, 200).unwrap();
+        bob.unlock(order_book, 5, 0).unwrap();
+        assert_totals(order_book, &[&alice, &bob]);
+
+        alice.withdraw(order_book, 10, 400).unwrap();
+        bob.withdraw(order_book, 5, 0).unwrap();
+        assert_totals(order_book, &[&alice, &bob]);
+        assert_eq!((alice.balances.quote_free, alice.balances.quote_locked), (200, 400));
+
+        // Locked funds can't be withdrawn, and a failed withdrawal changes nothing
+        let error = alice.withdraw(order_book, 0, 201).err().unwrap();
+        assert_eq!(error, ErrorCode::InsufficientBalance.into());
+        assert_totals(order_book, &[&alice, &bob]);
+        assert_eq!(order_book.totals.total().unwrap(), (0, 600));
+    }
+
+    #[test]
+    fn fee_volume_rolls_over_the_last_thirty_days() {
+        const DAY: i64 = 24 * 60 * 60;
+        let mut open_orders = Market::new(0, 0, 1).user(0);
+        open_orders.record_volume(100, 0);
+        open_orders.record_volume(50, 10 * DAY);
+        open_orders.record_volume(20, 10 * DAY + 5);
+        assert_eq!(open_orders.current_volume(10 * DAY), 170);
+
+        // Each day's volume drops out on its own, 30 days later
+        assert_eq!(open_orders.current_volume(30 * DAY - 1), 170);
+        assert_eq!(open_orders.current_volume(30 * DAY), 70);
+        assert_eq!(open_orders.current_volume(40 * DAY), 0);
+
+        // Recording clears the days skipped since the last fill and keeps the rest
+        open_orders.record_volume(5, 35 * DAY);
+        assert_eq!(open_orders.current_volume(35 * DAY), 75);
+
+        // A late fill counts if its day is still in the window
+        open_orders.record_volume(1, 20 * DAY);
+        open_orders.record_volume(1, 5 * DAY);
+        assert_eq!(open_orders.current_volume(35 * DAY), 76);
+
+        open_orders.record_volume(3, 500 * DAY);
+        assert_eq!(open_orders.current_volume(500 * DAY), 3);
+    }
+
+    #[test]
+    fn locked_buy_refunds_price_improvement() {
+        let mut market = Market::new(0, 0, 2);
+        market.deposit(0, 0, 1_000);
+        market.deposit(1, 10, 0);
+
+        let buy = market.rest(0, Side::Buy, 10, 5);
+        market.rest(1, Side::Sell, 8, 5);
+        assert_eq!(market.user(0).balances.quote_locked, 50);
+
+        let filled = market.rematch(0, buy);
+        let (buyer, seller) = (market.user(0), market.user(1));
+
+        // The buyer paid 8 per unit out of the 10 it locked and got the other 2 back
+        assert_eq!(filled.remaining_amount, 0);
+        assert_eq!(buyer.balances.base_free, 5);
+        assert_eq!(buyer.balances.quote_free, 960);
+        assert_eq!(buyer.balances.quote_locked, 0);
+        assert!(buyer.order_ids.is_empty());
+        assert_eq!(seller.balances.quote_free, 40);
+        assert_eq!(seller.balances.base_free, 5);
+        assert_eq!(seller.balances.base_locked, 0);
+        assert!(market.book().is_empty());
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn partially_filled_locked_buy_keeps_lock_for_remainder() {
+        let mut market = Market::new(0, 0, 2);
+        market.deposit(0, 0, 1_000);
+        market.deposit(1, 4, 0);
+
+        let buy = market.rest(0, Side::Buy, 10, 10);
+        market.rest(1, Side::Sell, 8, 4);
+        let rested = market.rematch(0, buy);
+        let buyer = market.user(0);
+
+        // 4 filled at 8 out of a lock of 10 each, and the other 6 still hold 10 each
+        assert_eq!(rested.remaining_amount, 6);
+        assert_eq!(buyer.balances.base_free, 4);
+        assert_eq!(buyer.balances.quote_free, 908);
+        assert_eq!(buyer.balances.quote_locked, 60);
+        assert_eq!(buyer.order_ids, vec![buy]);
+        assert_eq!(market.book().len(Side::Buy), 1);
+        assert_eq!(market.book().len(Side::Sell), 0);
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn locked_buy_pays_fees_out_of_its_lock() {
+        let mut market = Market::new(2, 5, 2);
+        market.deposit(0, 0, 100_000);
+        market.deposit(1, 5, 0);
+
+        let buy = market.rest(0, Side::Buy, 12_000, 5);
+        market.rest(1, Side::Sell, 10_000, 5);
+        // 60_000 at the limit price plus a 2 bps maker fee reserve
+        assert_eq!(market.user(0).balances.quote_locked, 60_012);
+
+        market.rematch(0, buy);
+        let (buyer, seller) = (market.user(0), market.user(1));
+
+        // The buyer pays 50_000 plus a 25 taker fee and the seller is paid 50_000 less a 10 maker fee
+        assert_eq!(buyer.balances.quote_free, 100_000 - 50_025);
+        assert_eq!(buyer.balances.quote_locked, 0);
+        assert_eq!(seller.balances.quote_free, 49_990);
+        assert_eq!(market.order_book.accrued_fees, 35);
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn locked_sell_is_paid_at_the_better_bid() {
+        let mut market = Market::new(0, 0, 2);
+        market.deposit(0, 5, 0);
+        market.deposit(1, 0, 100);
+
+        let sell = market.rest(0, Side::Sell, 8, 5);
+        market.rest(1, Side::Buy, 10, 5);
+        market.rematch(0, sell);
+        let (seller, buyer) = (market.user(0), market.user(1));
+
+        assert_eq!(seller.balances.base_locked, 0);
+        assert_eq!(seller.balances.base_free, 0);
+        assert_eq!(seller.balances.quote_free, 50);
+        assert_eq!(buyer.balances.base_free, 5);
+        assert_eq!(buyer.balances.quote_free, 50);
+        assert_eq!(buyer.balances.quote_locked, 0);
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn limit_fill_is_paid_from_escrow_once() {
+        let mut market = Market::new(0, 0, 2);
+        // The buyer can afford the order exactly once
+        market.deposit(0, 0, 50);
+        market.deposit(1, 5, 0);
+
+        market.rest(1, Side::Sell, 8, 5);
+        let order = market.limit(0, Side::Buy, 10, 5, TimeInForce::GoodTillCancelled).unwrap();
+        let (buyer, seller) = (market.user(0), market.user(1));
+
+        assert_eq!(order.filled_amount, 5);
+        assert_eq!(buyer.balances.base_free, 5);
+        assert_eq!(buyer.balances.quote_free, 10);
+        assert_eq!(buyer.balances.quote_locked, 0);
+        assert!(buyer.order_ids.is_empty());
+        assert_eq!(seller.balances.quote_free, 40);
+        assert_eq!(seller.balances.base_locked, 0);
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn crossing_buy_escrows_its_taker_fee() {
+        // 60_000 at 12_000 plus a 30 taker fee, which is more than the 12 maker fee reserve
+        for (deposit, filled) in [(60_030, true), (60_029, false)] {
+            let mut market = Market::new(2, 5, 2);
+            market.deposit(0, 0, deposit);
+            market.deposit(1, 5, 0);
+            market.rest(1, Side::Sell, 12_000, 5);
+
+            let result = market.limit(0, Side::Buy, 12_000, 5, TimeInForce::GoodTillCancelled);
+            let buyer = market.user(0);
+            if filled {
+                assert_eq!(result.unwrap().filled_amount, 5);
+                assert_eq!(buyer.balances.base_free, 5);
+                assert_eq!((buyer.balances.quote_free, buyer.balances.quote_locked), (0, 0));
+                assert_eq!(market.order_book.accrued_fees, 42);
+            } else {
+                assert_eq!(result.err().unwrap(), ErrorCode::InsufficientBalance.into());
+                assert_eq!((buyer.balances.quote_free, buyer.balances.quote_locked), (60_029, 0));
+            }
+            market.assert_conserved();
+        }
+    }
+
+    #[test]
+    fn resting_buy_needs_no_taker_fee_escrow() {
+        // 60_000 at 12_000 plus a 12 maker fee reserve, and nothing it could take
+        for time_in_force in [TimeInForce::GoodTillCancelled, TimeInForce::PostOnly, TimeInForce::PostOnlySlide] {
+            let mut market = Market::new(2, 5, 2);
+            market.deposit(0, 0, 60_012);
+            market.deposit(1, 5, 0);
+            market.rest(1, Side::Sell, 12_001, 5);
+
+            let result = market.limit(0, Side::Buy, 12_000, 5, time_in_force).unwrap();
+            let buyer = market.user(0);
+            assert_eq!((result.filled_amount, result.rested_amount), (0, 5));
+            assert_eq!((buyer.balances.quote_free, buyer.balances.quote_locked), (0, 60_012));
+            market.assert_conserved();
+        }
+    }
+
+    #[test]
+    fn unused_taker_fee_escrow_is_released_after_matching() {
+        let mut market = Market::new(2, 5, 2);
+        market.deposit(0, 0, 120_100);
+        market.deposit(1, 5, 0);
+        market.rest(1, Side::Sell, 12_000, 5);
+
+        // The order escrows 120_024 for its price and maker fee reserve and 36 more for its
+        // taker fee, of which the fill of 5 needs 18
+        let result = market.limit(0, Side::Buy, 12_000, 10, TimeInForce::GoodTillCancelled).unwrap();
+        let buyer = market.user(0);
+        assert_eq!((result.filled_amount, result.rested_amount), (5, 5));
+        assert_eq!(buyer.balances.quote_locked, 60_012);
+        assert_eq!(buyer.balances.quote_free, 120_100 - 60_030 - 60_012);
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn limit_order_needs_funds_for_its_whole_amount() {
+        let mut market = Market::new(0, 0, 2);
+        market.deposit(0, 0, 49);
+        market.deposit(1, 5, 0);
+
+        market.rest(1, Side::Sell, 8, 5);
+        let error = market.limit(0, Side::Buy, 10, 5, TimeInForce::GoodTillCancelled).err().unwrap();
+        assert_eq!(error, ErrorCode::InsufficientBalance.into());
+    }
+
+    #[test]
+    fn immediate_or_cancel_returns_unfilled_escrow() {
+        let mut market = Market::new(2, 5, 2);
+        market.deposit(0, 0, 1_000_000);
+        market.deposit(1, 3, 0);
+
+        market.rest(1, Side::Sell, 10_000, 3);
+        let order = market.limit(0, Side::Buy, 10_000, 5, TimeInForce::ImmediateOrCancel).unwrap();
+        let buyer = market.user(0);
+
+        assert_eq!(order.cancelled_amount, 2);
+        assert_eq!(buyer.balances.base_free, 3);
+        assert_eq!(buyer.balances.quote_free, 1_000_000 - 30_000 - 15);
+        assert_eq!(buyer.balances.quote_locked, 0);
+        assert!(buyer.order_ids.is_empty());
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn cancel_after_partial_fill_returns_the_rest() {
+        let mut market = Market::new(2, 5, 2);
+        market.deposit(0, 0, 1_000_000);
+        market.deposit(1, 7, 0);
+
+        let buy = market.limit(0, Side::Buy, 10_000, 10, TimeInForce::GoodTillCancelled).unwrap();
+        market.limit(1, Side::Sell, 10_000, 7, TimeInForce::GoodTillCancelled).unwrap();
+        market.assert_conserved();
+
+        market.cancel(0, buy.order_id);
+        let (buyer, seller) = (market.user(0), market.user(1));
+        assert_eq!(buyer.balances.quote_locked, 0);
+        assert_eq!(buyer.balances.base_free, 7);
+        assert_eq!(seller.balances.base_locked, 0);
+        assert!(market.book().is_empty());
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn makers_are_settled_when_events_are_consumed() {
+        let mut market = Market::new(0, 0, 2);
+        market.auto_consume = false;
+        market.deposit(0, 0, 50);
+        market.deposit(1, 5, 0);
+
+        let sell = market.rest(1, Side::Sell, 10, 5);
+        market.limit(0, Side::Buy, 10, 5, TimeInForce::GoodTillCancelled).unwrap();
+
+        // The taker is settled right away; the maker's fill and its order leaving the book wait
+        let (buyer, seller) = (market.user(0), market.user(1));
+        assert_eq!(buyer.balances.base_free, 5);
+        assert_eq!(seller.balances.base_locked, 5);
+        assert_eq!(seller.balances.quote_free, 0);
+        assert_eq!(seller.order_ids, vec![sell]);
+        assert_eq!(market.event_queue.len(), 2);
+        assert_eq!(market.order_book.totals.quote_free, 50);
+
+        assert_eq!(market.consume_events(1), 1);
+        let seller = market.user(1);
+        assert_eq!((seller.balances.base_locked, seller.balances.quote_free), (0, 50));
+        assert_eq!(seller.order_ids, vec![sell]);
+
+        assert_eq!(market.consume_events(10), 1);
+        assert!(market.user(1).order_ids.is_empty());
+        assert!(market.event_queue.is_empty());
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn consuming_stops_at_a_missing_maker() {
+        let mut market = Market::new(0, 0, 2);
+        market.auto_consume = false;
+        market.deposit(0, 0, 50);
+        market.deposit(1, 5, 0);
+
+        market.rest(1, Side::Sell, 10, 5);
+        market.limit(0, Side::Buy, 10, 2, TimeInForce::GoodTillCancelled).unwrap();
+
+        let mut taker = market.user(0);
+        assert_eq!(market.event_queue.consume(&mut [&mut taker], 10).unwrap(), 0);
+        assert_eq!(market.event_queue.len(), 1);
+        assert_eq!(market.consume_events(10), 1);
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn migrated_orders_settle_once_their_owner_has_open_orders() {
+        let mut market = Market::new(0, 0, 2);
+        market.auto_consume = false;
+        let maker = market.user(1).owner;
+        // A legacy balance and two legacy asks, held in the vaults before the maker had an
+        // open orders account
+        let legacy_ask = |id: u64, price: u64, amount: u64| LegacyOrder {
+            id, owner: maker, side: Side::Sell, price, original_amount: amount, remaining_amount: amount, created_at: 0,
+        };
+        market.order_book.user_balances = vec![UserBalance { owner: maker, base_amount: 1, quote_amount: 4 }];
+        market.order_book.order_counter = 2;
+        (market.base_deposited, market.quote_deposited) = (8, 4);
+        let mut book = Book::load(bytemuck::cast_slice_mut(&mut market.slab)).unwrap();
+        market.order_book.migrate_legacy_orders(&mut book, &[legacy_ask(0, 10, 5), legacy_ask(1, 9, 2)]).unwrap();
+        assert_eq!(market.order_book.totals.base_locked, 7);
+
+        // Takes all of the ask at 9 and part of the one at 10
+        market.deposit(0, 0, 100);
+        market.limit(0, Side::Buy, 10, 5, TimeInForce::ImmediateOrCancel).unwrap();
+        let mut taker = market.user(0);
+        assert_eq!(market.event_queue.consume(&mut [&mut taker], 10).unwrap(), 0);
+
+        let mut open_orders = market.user(1);
+        let book = Book::load(bytemuck::cast_slice_mut(&mut market.slab)).unwrap();
+        open_orders.claim_legacy_funds(&mut market.order_book, &book, &market.event_queue).unwrap();
+        market.save(1, &open_orders);
+        assert_eq!(market.consume_events(10), 3);
+
+        let maker = market.user(1);
+        assert_eq!((maker.balances.base_free, maker.balances.base_locked), (1, 2));
+        assert_eq!(maker.balances.quote_free, 4 + 18 + 30);
+        assert_eq!(maker.order_ids, vec![0]);
+        assert!(market.order_book.user_balances.is_empty());
+        market.assert_conserved();
+    }
+
+    #[test]
+    fn matching_stops_when_the_event_queue_is_full() {
+        let mut market = Market::new(0, 0, 2);
+        market.auto_consume = false;
+        market.deposit(0, 0, 1_000);
+        market.deposit(1, 100, 0);
+
+        // Each filled order leaves a fill and an out event
+        let order_count = EVENT_QUEUE_CAPACITY / 2 + 1;
+        for _ in 0..order_count {
+            market.rest(1, Side::Sell, 10, 1);
+        }
+        let error = market.limit(0, Side::Buy, 10, order_count as u64, TimeInForce::GoodTillCancelled).err().unwrap();
+        assert_eq!(error, ErrorCode::EventQueueFull.into());
+    }
+
+    #[test]
+    fn fills_are_recorded_in_the_trade_history() {
+        let mut market = Market::new(0, 0, 2);
+        market.deposit(0, 0, 1_000);
+        market.deposit(1, 100, 0);
+
+        market.rest(1, Side::Sell, 10, 3);
+        market.rest(1, Side::Sell, 11, 3);
+        market.limit(0, Side::Buy, 11, 4, TimeInForce::GoodTillCancelled).unwrap();
+
+        let (buyer, seller) = (market.user(0).owner, market.user(1).owner);
+        let trades: Vec<(u64, u64)> = market.trade_history.recent().map(|trade| (trade.price, trade.amount)).collect();
+        assert_eq!(trades, vec![(11, 1), (10, 3)]);
+        let latest = market.trade_history.recent().next().unwrap();
+        assert_eq!((latest.maker, latest.taker), (seller, buyer));
+        assert!(latest.taker_side() == Side::Buy);
+    }
+
+    #[test]
+    fn fills_update_the_candles_of_their_intervals() {
+        let mut market = Market::new(0, 0, 2);
+        market.deposit(0, 0, 1_000);
+        market.deposit(1, 100, 0);
+
+        for (now, price) in [(30, 10), (50, 12), (59, 9), (61, 11)] {
+            market.clock.unix_timestamp = now;
+            market.rest(1, Side::Sell, price, 1);
+            market.limit(0, Side::Buy, price, 1, TimeInForce::ImmediateOrCancel).unwrap();
+        }
+
+        let ohlcv = |candle: &Candle| (candle.start_time, candle.open, candle.high, candle.low, candle.close, candle.base_volume, candle.quote_volume);
+        let minutes: Vec<_> = market.candles.minutes.recent().map(ohlcv).collect();
+        assert_eq!(minutes, vec![(60, 11, 11, 11, 11, 1, 11), (0, 10, 12, 9, 9, 3, 31)]);
+        let hours: Vec<_> = market.candles.hours.recent().map(ohlcv).collect();
+        assert_eq!(hours, vec![(0, 10, 12, 9, 11, 4, 42)]);
+        assert_eq!(market.candles.days.len(), 1);
+    }
+
+    #[test]
+    fn conserves_funds_across_random_trading() {
+        let mut market = Market::new(2, 5, 4);
+        market.order_book.fee_tiers = vec![FeeTier { min_volume: 200_000, maker_fee_bps: -1, taker_fee_bps: 3 }];
+        for user in 0..4 {
+            market.deposit(user, 1_000_000, 1_000_000_000);
+        }
+
+        // A fixed linear congruential generator keeps the sequence reproducible
+        let mut seed: u64 = 0x5eed;
+        let mut next = |bound: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % bound
+        };
+
+        for _ in 0..500 {
+            let user = next(4) as usize;
+            let side = if next(2) == 0 { Side::Buy } else { Side::Sell };
+            let price = 9_900 + next(200);
+            let amount = 1 + next(20);
+            let open_orders = market.user(user);
+
+            match next(5) {
+                0 if !open_orders.order_ids.is_empty() => {
+                    let order_id = open_orders.order_ids[next(open_orders.order_ids.len() as u64) as usize];
+                    market.cancel(user, order_id);
+                },
+                1 => {
+                    market.market(user, side, amount, if side == Side::Buy { 10_100 } else { 9_900 }, 100_000).unwrap();
+                },
+                2 => {
+                    market.limit(user, side, price, amount, TimeInForce::ImmediateOrCancel).unwrap();
+                },
+                _ if open_orders.order_ids.len() < 10 => {
+                    market.limit(user, side, price, amount, TimeInForce::GoodTillCancelled).unwrap();
+                },
+                _ => continue,
+            }
+            market.assert_conserved();
+        }
+        assert!(market.order_book.accrued_fees > 0);
+    }
+}
@@ -0,0 +1,604 @@
+use std::mem::size_of;
+
+use anchor_lang::prelude::*;
+
+use crate::constants::FEE_BPS_DENOMINATOR;
+use crate::error::ErrorCode;
+use crate::events::OrderCancelled;
+use crate::math::{fee_amount, quote_amount, Rounding};
+use crate::state::{Order, SelfTradePrevention, Side, Traders};
+
+// Marks the end of the free list
+pub const NIL: u32 = u32::MAX;
+
+// Resting orders of an orderbook, kept in a zero-copy account so that instructions only
+// touch the slots they need instead of deserializing the whole book.
+//
+// The header is followed by the order slots and the two side indexes:
+//   [discriminator][OrderSlab][OrderSlot; 2 * capacity][u32; capacity][u32; capacity]
+// Each index holds the slot numbers of one side in price-time priority, best price first.
+// Unused slots are linked into a free list through OrderSlot::next_free.
+#[account(zero_copy)]
+pub struct OrderSlab {
+    pub order_book: Pubkey,
+    pub free_head: u32,
+    pub bid_count: u32,
+    pub ask_count: u32,
+    pub bump: u8,
+    pub padding: [u8; 3],
+}
+
+#[zero_copy]
+pub struct OrderSlot {
+    pub id: u64,
+    pub owner: Pubkey,
+    pub price: u64,
+    pub original_amount: u64,
+    pub remaining_amount: u64,
+    pub created_at: i64,
+    pub next_free: u32,
+    pub side: u8,
+    pub self_trade_prevention: u8,
+    pub maker_fee_bps: i16,
+}
+
+impl OrderSlab {
+    // Each unit of capacity adds a slot per side and an entry in each side index
+    const BYTES_PER_ORDER: usize = 2 * size_of::<OrderSlot>() + 2 * size_of::<u32>();
+
+    // Account size needed to hold `order_capacity` orders on each side of the book
+    pub fn space(order_capacity: u16) -> usize {
+        8 + size_of::<OrderSlab>() + order_capacity as usize * Self::BYTES_PER_ORDER
+    }
+
+    // Number of orders per side that fits in an account of `data_len` bytes
+    pub fn capacity_for_len(data_len: usize) -> usize {
+        data_len.saturating_sub(8 + size_of::<OrderSlab>()) / Self::BYTES_PER_ORDER
+    }
+}
+
+impl From<Order> for OrderSlot {
+    fn from(order: Order) -> Self {
+        OrderSlot {
+            id: order.id,
+            owner: order.owner,
+            price: order.price,
+            original_amount: order.original_amount,
+            remaining_amount: order.remaining_amount,
+            created_at: order.created_at,
+            next_free: NIL,
+            side: order.side as u8,
+            self_trade_prevention: order.self_trade_prevention as u8,
+            maker_fee_bps: order.maker_fee_bps,
+        }
+    }
+}
+
+impl OrderSlot {
+    pub fn to_order(&self) -> Order {
+        Order {
+            id: self.id,
+            owner: self.owner,
+            side: match self.side {
+                0 => Side::Buy,
+                _ => Side::Sell,
+            },
+            price: self.price,
+            original_amount: self.original_amount,
+            remaining_amount: self.remaining_amount,
+            created_at: self.created_at,
+            self_trade_prevention: match self.self_trade_prevention {
+                0 => SelfTradePrevention::CancelTaker,
+                1 => SelfTradePrevention::CancelMaker,
+                2 => SelfTradePrevention::CancelBoth,
+                _ => SelfTradePrevention::DecrementAndCancel,
+            },
+            maker_fee_bps: self.maker_fee_bps,
+        }
+    }
+}
+
+// Totals produced by matching a single order against the book
+#[derive(Clone, Copy, Default)]
+pub struct MatchOutcome {
+    pub filled_amount: u64,
+    pub filled_quote: u64,
+    pub cancelled_amount: u64,
+    pub taker_fee: u64,
+}
+
+// Mutable view over the data of an order slab account
+pub struct Book<'a> {
+    pub header: &'a mut OrderSlab,
+    slots: &'a mut [OrderSlot],
+    bids: &'a mut [u32],
+    asks: &'a mut [u32],
+}
+
+impl<'a> Book<'a> {
+    // View the account data of an order slab, discriminator included. The capacity
+    // is taken from the size of the account.
+    pub fn load(data: &'a mut [u8]) -> Result<Book<'a>> {
+        let capacity = OrderSlab::capacity_for_len(data.len());
+        Self::load_with_capacity(data, capacity)
+    }
+
+    fn load_with_capacity(data: &'a mut [u8], capacity: usize) -> Result<Book<'a>> {
+        let slots_len = 2 * capacity * size_of::<OrderSlot>();
+        let index_len = capacity * size_of::<u32>();
+        require!(
+            data.len() >= 8 + size_of::<OrderSlab>() + slots_len + 2 * index_len,
+            anchor_lang::error::ErrorCode::AccountDidNotDeserialize
+        );
+
+        let (header, rest) = data[8..].split_at_mut(size_of::<OrderSlab>());
+        let (slots, rest) = rest.split_at_mut(slots_len);
+        let (bids, rest) = rest.split_at_mut(index_len);
+        let asks = &mut rest[..index_len];
+
+        Ok(Book {
+            header: bytemuck::try_from_bytes_mut(header)
+                .map_err(|_| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))?,
+            slots: bytemuck::try_cast_slice_mut(slots)
+                .map_err(|_| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))?,
+            bids: bytemuck::try_cast_slice_mut(bids)
+                .map_err(|_| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))?,
+            asks: bytemuck::try_cast_slice_mut(asks)
+                .map_err(|_| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))?,
+        })
+    }
+
+    // Set up an empty book with every slot on the free list
+    pub fn initialize(&mut self, order_book: Pubkey, bump: u8) {
+        self.header.order_book = order_book;
+        self.header.bid_count = 0;
+        self.header.ask_count = 0;
+        self.header.bump = bump;
+        self.rebuild_free_list();
+    }
+
+    // Maximum number of resting orders on each side
+    pub fn capacity(&self) -> usize {
+        self.bids.len()
+    }
+
+    // Number of orders resting on one side
+    pub fn len(&self, side: Side) -> usize {
+        match side {
+            Side::Buy => self.header.bid_count as usize,
+            Side::Sell => self.header.ask_count as usize,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.header.bid_count == 0 && self.header.ask_count == 0
+    }
+
+    // Slot numbers of the orders resting on one side, best price first
+    fn index(&self, side: Side) -> &[u32] {
+        let len = self.len(side);
+        match side {
+            Side::Buy => &self.bids[..len],
+            Side::Sell => &self.asks[..len],
+        }
+    }
+
+    // The order at a position of one side, counted from the best price
+    pub fn order_at(&self, side: Side, index: usize) -> Order {
+        self.slots[self.index(side)[index] as usize].to_order()
+    }
+
+    // Orders resting on one side, best price first
+    pub fn orders(&self, side: Side) -> impl Iterator<Item = Order> + '_ {
+        self.index(side).iter().map(|slot| self.slots[*slot as usize].to_order())
+    }
+
+    // Find a resting order by ID, returning the side it rests on and its index
+    pub fn find_order(&self, order_id: u64) -> Option<(Side, usize)> {
+        for side in [Side::Buy, Side::Sell] {
+            if let Some(i) = self.index(side).iter().position(|slot| self.slots[*slot as usize].id == order_id) {
+                return Some((side, i));
+            }
+        }
+        None
+    }
+
+    // Remove a resting order from the given side of the book and free its slot
+    pub fn remove_order(&mut self, side: Side, index: usize) -> Order {
+        let len = self.len(side);
+        let (orders, count) = match side {
+            Side::Buy => (&mut *self.bids, &mut self.header.bid_count),
+            Side::Sell => (&mut *self.asks, &mut self.header.ask_count),
+        };
+        let slot = orders[index] as usize;
+        orders.copy_within(index + 1..len, index);
+        *count -= 1;
+
+        let order = self.slots[slot].to_order();
+        self.slots[slot].next_free = self.header.free_head;
+        self.header.free_head = slot as u32;
+        order
+    }
+
+    // Remove every resting order owned by a user, optionally limited to one side
+    pub fn remove_orders_by_owner(&mut self, owner: &Pubkey, side: Option<Side>) -> Vec<Order> {
+        let mut removed = Vec::new();
+        for book_side in [Side::Buy, Side::Sell] {
+            if side.is_some_and(|side| side != book_side) {
+                continue;
+            }
+            let mut i = 0;
+            while i < self.len(book_side) {
+                if self.order_at(book_side, i).owner == *owner {
+                    removed.push(self.remove_order(book_side, i));
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        removed
+    }
+
+    // Rest an order on its side of the book, keeping price-time priority: buys are sorted
+    // by price descending and sells ascending, with older order IDs first within a price level
+    pub fn insert_order(&mut self, order: Order) -> Result<()> {
+        let len = self.len(order.side);
+        let slot = self.header.free_head;
+        if len >= self.capacity() || slot == NIL {
+            return err!(ErrorCode::OrderbookFull);
+        }
+
+        // Take the first free slot
+        self.header.free_head = self.slots[slot as usize].next_free;
+        self.slots[slot as usize] = OrderSlot::from(order);
+
+        let slots = &*self.slots;
+        let (orders, count) = match order.side {
+            Side::Buy => (&mut *self.bids, &mut self.header.bid_count),
+            Side::Sell => (&mut *self.asks, &mut self.header.ask_count),
+        };
+        let index = orders[..len].partition_point(|resting| {
+            let resting = &slots[*resting as usize];
+            let better_price = match order.side {
+                Side::Buy => resting.price > order.price,
+                Side::Sell => resting.price < order.price,
+            };
+            better_price || (resting.price == order.price && resting.id < order.id)
+        });
+        orders.copy_within(index..len, index + 1);
+        orders[index] = slot;
+        *count += 1;
+        Ok(())
+    }
+
+    // Highest resting buy price
+    pub fn best_bid(&self) -> Option<u64> {
+        self.orders(Side::Buy).next().map(|bid| bid.price)
+    }
+
+    // Lowest resting sell price
+    pub fn best_ask(&self) -> Option<u64> {
+        self.orders(Side::Sell).next().map(|ask| ask.price)
+    }
+
+    // Total resting amount on the opposite side that an order at this price could fill against
+    pub fn fillable_amount(&self, side: Side, price: u64) -> Result<u64> {
+        // Both sides are kept best price first, so stop at the first order that doesn't cross
+        let mut total: u64 = 0;
+        for resting in self.orders(side.opposite()) {
+            let crosses = match side {
+                Side::Buy => price >= resting.price,
+                Side::Sell => price <= resting.price,
+            };
+            if !crosses {
+                break;
+            }
+            total = total.checked_add(resting.remaining_amount)
+                .ok_or(error!(ErrorCode::CalculationFailure))?;
+        }
+        Ok(total)
+    }
+
+    // Overwrite the remaining amount of a resting order
+    fn set_remaining(&mut self, side: Side, index: usize, remaining_amount: u64) {
+        let slot = self.index(side)[index] as usize;
+        self.slots[slot].remaining_amount = remaining_amount;
+    }
+
+    // Reduce a resting order by `amount` without filling it, returning the funds that part
+    // was holding to its owner. The order is removed once nothing remains.
+    pub fn cancel_resting(&mut self, traders: &mut Traders, side: Side, index: usize, amount: u64) -> Result<()> {
+        let resting = self.order_at(side, index);
+        let (base_amount, quote_amount) = resting.released_amounts(amount, traders.base_lot_size())?;
+
+        traders.unlock(&resting.owner, base_amount, quote_amount)?;
+
+        let remaining_amount = resting.remaining_amount.checked_sub(amount)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        if remaining_amount == 0 {
+            self.remove_order(side, index);
+            traders.get_mut(&resting.owner)?.remove_order_id(resting.id);
+        } else {
+            self.set_remaining(side, index, remaining_amount);
+        }
+
+        emit!(OrderCancelled {
+            order_book: traders.order_book_key(),
+            order_id: resting.id,
+            owner: resting.owner,
+            side,
+            cancelled_amount: amount,
+            remaining_amount,
+        });
+        Ok(())
+    }
+
+    // Match an order against the opposite side of the book, settling each fill between
+    // the order's owner and the resting order's owner. The taker pays as the traders'
+    // TakerFunds say and the maker from the funds its order locked. A buy stops once the
+    // quote and taker fee it pays would exceed quote_limit, if one is given. The order's
+    // remaining_amount is updated in place; any part cancelled by self-trade prevention is
+    // reported separately.
+    pub fn match_order(&mut self, traders: &mut Traders, order: &mut Order, quote_limit: Option<u64>) -> Result<MatchOutcome> {
+        let taker = order.owner;
+        let resting_side = order.side.opposite();
+        let taker_fee_bps = traders.taker_fee_bps();
+        let base_lot_size = traders.base_lot_size();
+        let mut outcome = MatchOutcome::default();
+
+        // The opposite side is kept in price-time priority, so walk it from the front
+        let mut i = 0;
+        while order.remaining_amount > 0 && i < self.len(resting_side) {
+            let resting = self.order_at(resting_side, i);
+
+            // Stop at the first resting order the price no longer crosses
+            let crosses = match order.side {
+                Side::Buy => order.price >= resting.price,
+                Side::Sell => order.price <= resting.price,
+            };
+            if !crosses {
+                break;
+            }
+
+            // Self-trade prevention is applied before any balance is touched
+            if resting.owner == taker {
+                match order.self_trade_prevention {
+                    SelfTradePrevention::CancelTaker => {
+                        traders.release_taker(order, order.remaining_amount)?;
+                        outcome.cancelled_amount = outcome.cancelled_amount
+                            .checked_add(order.remaining_amount)
+                            .ok_or(ErrorCode::CalculationFailure)?;
+                        order.remaining_amount = 0;
+                    },
+                    SelfTradePrevention::CancelMaker => {
+                        self.cancel_resting(traders, resting_side, i, resting.remaining_amount)?;
+                    },
+                    SelfTradePrevention::CancelBoth => {
+                        self.cancel_resting(traders, resting_side, i, resting.remaining_amount)?;
+                        traders.release_taker(order, order.remaining_amount)?;
+                        outcome.cancelled_amount = outcome.cancelled_amount
+                            .checked_add(order.remaining_amount)
+                            .ok_or(ErrorCode::CalculationFailure)?;
+                        order.remaining_amount = 0;
+                    },
+                    SelfTradePrevention::DecrementAndCancel => {
+                        // Shrink both orders by the overlap; whichever reaches zero is cancelled
+                        let overlap = order.remaining_amount.min(resting.remaining_amount);
+                        self.cancel_resting(traders, resting_side, i, overlap)?;
+                        traders.release_taker(order, overlap)?;
+                        order.remaining_amount = order.remaining_amount.checked_sub(overlap)
+                            .ok_or(ErrorCode::CalculationFailure)?;
+                        outcome.cancelled_amount = outcome.cancelled_amount.checked_add(overlap)
+                            .ok_or(ErrorCode::CalculationFailure)?;
+                    }
+                }
+                continue;
+            }
+
+            // Calculate the matched amount
+            let mut match_amount = order.remaining_amount.min(resting.remaining_amount);
+
+            // Only take as much as the remaining quote budget can pay for
+            if let Some(quote_limit) = quote_limit {
+                let spent = outcome.filled_quote.checked_add(outcome.taker_fee)
+                    .ok_or(ErrorCode::CalculationFailure)?;
+                let budget = quote_limit.checked_sub(spent)
+                    .ok_or(ErrorCode::CalculationFailure)?;
+                // Each lot costs its price plus the taker fee on it
+                let lot_cost = resting.price as u128 * (FEE_BPS_DENOMINATOR as u128 + taker_fee_bps as u128);
+                let affordable_lots = budget as u128 * FEE_BPS_DENOMINATOR as u128 / lot_cost;
+                let affordable = affordable_lots.saturating_mul(base_lot_size as u128);
+                match_amount = match_amount.min(u64::try_from(affordable).unwrap_or(u64::MAX));
+
+                // The taker fee rounds up, which can push the estimate one lot over budget
+                let cost = quote_amount(resting.price, match_amount, base_lot_size)?;
+                let cost = cost.checked_add(fee_amount(cost, taker_fee_bps, Rounding::Up)?)
+                    .ok_or(ErrorCode::CalculationFailure)?;
+                if cost > budget {
+                    match_amount = match_amount.saturating_sub(base_lot_size);
+                }
+                if match_amount == 0 {
+                    break;
+                }
+            }
+
+            // Calculate quote amount at the resting order's price
+            let quote_amount = quote_amount(resting.price, match_amount, base_lot_size)?;
+
+            // Update balances in the traders' open orders accounts, while both orders still
+            // show what they held before this fill
+            let taker_fee = traders.settle_fill(order, &resting, match_amount, quote_amount)?;
+            outcome.taker_fee = outcome.taker_fee.checked_add(taker_fee)
+                .ok_or(ErrorCode::CalculationFailure)?;
+
+            // Update the order amounts, removing the resting order once it is filled. Its
+            // owner stops tracking it when the fill's events are consumed.
+            order.remaining_amount = order.remaining_amount.checked_sub(match_amount)
+                .ok_or(ErrorCode::CalculationFailure)?;
+            outcome.filled_amount = outcome.filled_amount.checked_add(match_amount)
+                .ok_or(ErrorCode::CalculationFailure)?;
+            outcome.filled_quote = outcome.filled_quote.checked_add(quote_amount)
+                .ok_or(ErrorCode::CalculationFailure)?;
+
+            let resting_remaining = resting.remaining_amount.checked_sub(match_amount)
+                .ok_or(ErrorCode::CalculationFailure)?;
+            if resting_remaining == 0 {
+                self.remove_order(resting_side, i);
+            } else {
+                self.set_remaining(resting_side, i, resting_remaining);
+                i += 1;
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    // Link every slot that neither side index refers to into the free list
+    fn rebuild_free_list(&mut self) {
+        let mut used = vec![false; self.slots.len()];
+        for slot in self.index(Side::Buy).iter().chain(self.index(Side::Sell)) {
+            used[*slot as usize] = true;
+        }
+
+        let mut free_head = NIL;
+        for slot in (0..self.slots.len()).rev() {
+            if !used[slot] {
+                self.slots[slot].next_free = free_head;
+                free_head = slot as u32;
+            }
+        }
+        self.header.free_head = free_head;
+    }
+
+    // Move every resting order into the first `slot_count` slots
+    fn compact(&mut self, slot_count: usize) -> Result<()> {
+        let mut used = vec![false; self.slots.len()];
+        for slot in self.index(Side::Buy).iter().chain(self.index(Side::Sell)) {
+            used[*slot as usize] = true;
+        }
+        let mut free_slots = (0..slot_count).filter(|slot| !used[*slot]).collect::<Vec<_>>().into_iter();
+
+        let (bid_count, ask_count) = (self.len(Side::Buy), self.len(Side::Sell));
+        let entries = self.bids[..bid_count].iter_mut().chain(self.asks[..ask_count].iter_mut());
+        for entry in entries {
+            if *entry as usize >= slot_count {
+                let target = free_slots.next().ok_or(ErrorCode::InvalidOrderCapacity)?;
+                self.slots[target] = self.slots[*entry as usize];
+                *entry = target as u32;
+            }
+        }
+        Ok(())
+    }
+
+    // Rearrange the data of an order slab from one capacity to another. The data has to be
+    // large enough for both layouts, so grow the account before calling this and shrink it
+    // after. Both sides have to fit within the new capacity.
+    pub fn relayout(data: &mut [u8], old_capacity: usize, new_capacity: usize) -> Result<()> {
+        let (bid_len, ask_len) = {
+            let book = Book::load_with_capacity(data, old_capacity)?;
+            (book.len(Side::Buy), book.len(Side::Sell))
+        };
+        require!(
+            bid_len <= new_capacity && ask_len <= new_capacity,
+            ErrorCode::InvalidOrderCapacity
+        );
+
+        // Orders in slots past the end of the new slot array are moved into free slots first
+        if new_capacity < old_capacity {
+            Book::load_with_capacity(data, old_capacity)?.compact(2 * new_capacity)?;
+        }
+
+        let slots_start = 8 + size_of::<OrderSlab>();
+        let bids_at = |capacity: usize| slots_start + 2 * capacity * size_of::<OrderSlot>();
+        let asks_at = |capacity: usize| bids_at(capacity) + capacity * size_of::<u32>();
+        let bid_bytes = bid_len * size_of::<u32>();
+        let ask_bytes = ask_len * size_of::<u32>();
+
+        // Move the side indexes in an order that never overwrites one before it is copied
+        let old_bids = bids_at(old_capacity)..bids_at(old_capacity) + bid_bytes;
+        let old_asks = asks_at(old_capacity)..asks_at(old_capacity) + ask_bytes;
+        if new_capacity > old_capacity {
+            data.copy_within(old_asks, asks_at(new_capacity));
+            data.copy_within(old_bids, bids_at(new_capacity));
+        } else {
+            data.copy_within(old_bids, bids_at(new_capacity));
+            data.copy_within(old_asks, asks_at(new_capacity));
+        }
+
+        Book::load_with_capacity(data, new_capacity)?.rebuild_free_list();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_market::Market;
+    use crate::state::{EventKind, TimeInForce};
+
+    fn order_ids(market: &mut Market, side: Side) -> Vec<u64> {
+        market.book().orders(side).map(|order| order.id).collect()
+    }
+
+    #[test]
+    fn sides_are_kept_in_price_time_priority() {
+        let mut market = Market::new(0, 0, 1);
+        market.deposit(0, 100, 1_000);
+
+        let bids: Vec<u64> = [10, 12, 10, 11].iter().map(|price| market.rest(0, Side::Buy, *price, 1)).collect();
+        let asks: Vec<u64> = [20, 18, 20, 19].iter().map(|price| market.rest(0, Side::Sell, *price, 1)).collect();
+
+        // Bids highest first and asks lowest first, older orders first within a price
+        assert_eq!(order_ids(&mut market, Side::Buy), vec![bids[1], bids[3], bids[0], bids[2]]);
+        assert_eq!(order_ids(&mut market, Side::Sell), vec![asks[1], asks[3], asks[0], asks[2]]);
+        assert_eq!(market.book().best_bid(), Some(12));
+        assert_eq!(market.book().best_ask(), Some(18));
+
+        // Removing an order keeps the others in place
+        market.cancel(0, bids[3]);
+        assert_eq!(order_ids(&mut market, Side::Buy), vec![bids[1], bids[0], bids[2]]);
+    }
+
+    #[test]
+    fn fillable_amount_counts_only_crossing_orders() {
+        let mut market = Market::new(0, 0, 1);
+        market.deposit(0, 100, 1_000);
+        market.rest(0, Side::Sell, 18, 2);
+        market.rest(0, Side::Sell, 19, 3);
+        market.rest(0, Side::Sell, 20, 4);
+        market.rest(0, Side::Buy, 15, 5);
+
+        let book = market.book();
+        assert_eq!(book.fillable_amount(Side::Buy, 17).unwrap(), 0);
+        assert_eq!(book.fillable_amount(Side::Buy, 19).unwrap(), 5);
+        assert_eq!(book.fillable_amount(Side::Buy, 25).unwrap(), 9);
+        assert_eq!(book.fillable_amount(Side::Sell, 15).unwrap(), 5);
+        assert_eq!(book.fillable_amount(Side::Sell, 16).unwrap(), 0);
+    }
+
+    #[test]
+    fn fills_take_better_prices_first_then_older_orders() {
+        let mut market = Market::new(0, 0, 4);
+        market.auto_consume = false;
+        market.deposit(0, 0, 10_000);
+        for maker in 1..4 {
+            market.deposit(maker, 10, 0);
+        }
+
+        let first = market.rest(1, Side::Sell, 11, 2);
+        let second = market.rest(2, Side::Sell, 11, 2);
+        let better = market.rest(3, Side::Sell, 10, 2);
+        let third = market.rest(3, Side::Sell, 11, 2);
+        market.limit(0, Side::Buy, 11, 7, TimeInForce::GoodTillCancelled).unwrap();
+
+        let mut fills = Vec::new();
+        while let Some(event) = market.event_queue.pop_front() {
+            if event.kind() == EventKind::Fill {
+                fills.push((event.order_id, event.base_released));
+            }
+        }
+        assert_eq!(fills, vec![(better, 2), (first, 2), (second, 2), (third, 1)]);
+        assert_eq!(order_ids(&mut market, Side::Sell), vec![third]);
+        assert_eq!(market.book().order_at(Side::Sell, 0).remaining_amount, 1);
+    }
+}
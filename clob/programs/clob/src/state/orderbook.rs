@@ -1,6 +1,10 @@
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
 
+use crate::constants::{FEE_BPS_DENOMINATOR, MAX_FEE_BPS, MAX_FEE_TIERS};
 use crate::error::ErrorCode;
+use crate::math::{fee_amount, quote_amount, Rounding};
+use crate::state::{Balances, Book};
 
 #[account]
 #[derive(InitSpace)]
@@ -11,16 +15,51 @@ pub struct Orderbook {
     pub quote_vault: Pubkey,
     pub base_decimals: u8,
     pub quote_decimals: u8,
-    #[max_len(10, Order)]
-    pub buys: Vec<Order>,
-    #[max_len(10, Order)]
-    pub sells: Vec<Order>,
     pub authority: Pubkey,
     pub order_counter: u64,
+    // Balances now live in each user's OpenOrders account. Entries left here from before
+    // are moved across when that account is created.
     #[max_len(20, UserBalance)]
     pub user_balances: Vec<UserBalance>,
     pub bump: u8,
     pub is_delegated: bool,
+    // Maximum number of resting orders on each side of the book, see OrderSlab::space
+    pub order_capacity: u16,
+    // Sum of every user's free and locked balances on this orderbook
+    pub totals: Balances,
+    // Fees charged on the quote amount of each fill, in basis points, for users below
+    // every fee tier. A negative maker fee is a rebate.
+    pub maker_fee_bps: i16,
+    pub taker_fee_bps: u16,
+    // Quote collected as fees and not yet swept to the treasury
+    pub accrued_fees: u64,
+    // Lower fee rates for users with more traded volume, sorted by min_volume
+    #[max_len(MAX_FEE_TIERS)]
+    pub fee_tiers: Vec<FeeTier>,
+    // Part of each taker fee credited to the referrer passed with the order, in basis
+    // points of the fee
+    pub referral_share_bps: u16,
+    // Prices are quote atoms per base lot and must be a multiple of tick_size. Amounts are
+    // base atoms and must be whole lots of at least min_order_size.
+    pub tick_size: u64,
+    pub base_lot_size: u64,
+    pub min_order_size: u64,
+    // Price of the latest fill, and volume and number of fills over the book's lifetime.
+    // What rests on the book is in the locked part of totals.
+    pub last_price: u64,
+    pub base_volume: u128,
+    pub quote_volume: u128,
+    pub trade_count: u64,
+}
+
+// Fee rates that apply once a user's quote volume traded over the last FEE_VOLUME_DAYS
+// days reaches min_volume. The window rolls daily, so a tier lapses gradually as old
+// days drop out rather than all at once.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct FeeTier {
+    pub min_volume: u64,
+    pub maker_fee_bps: i16,
+    pub taker_fee_bps: u16,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
@@ -31,94 +70,166 @@ pub struct UserBalance {
 }
 
 impl Orderbook {
-    pub const MAX_ORDERS: usize = 100;
-
     pub fn next_order_id(&mut self) -> u64 {
         let id = self.order_counter;
         self.order_counter += 1;
         id
     }
 
-    pub fn add_balance(&mut self, owner: &Pubkey, base_amount: u64, quote_amount: u64) -> Result<()> {
+    pub fn record_trade(&mut self, price: u64, base_amount: u64, quote_amount: u64) {
+        self.last_price = price;
+        self.base_volume = self.base_volume.saturating_add(base_amount as u128);
+        self.quote_volume = self.quote_volume.saturating_add(quote_amount as u128);
+        self.trade_count += 1;
+    }
 
-        for balance in &mut self.user_balances {
-            if balance.owner == *owner {
-                if base_amount > 0 {
-                    balance.base_amount = balance.base_amount
-                        .checked_add(base_amount)
-                        .ok_or(error!(ErrorCode::CalculationFailure))?;
-                }
-                
-                if quote_amount > 0 {
-                    balance.quote_amount = balance.quote_amount
-                        .checked_add(quote_amount)
-                        .ok_or(error!(ErrorCode::CalculationFailure))?;
+    // The vaults have to hold at least what users are owed. Tokens sent straight to a
+    // vault belong to nobody, so requiring an exact match would let anyone block the book.
+    pub fn check_vault_balances(&self, base_vault_amount: u64, quote_vault_amount: u64) -> Result<()> {
+        let (base_amount, quote_amount) = self.totals.total()?;
+        let quote_amount = quote_amount.checked_add(self.accrued_fees)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        require!(
+            base_vault_amount >= base_amount && quote_vault_amount >= quote_amount,
+            ErrorCode::VaultBalanceMismatch
+        );
+        Ok(())
+    }
+
+    // Check the accounting of the whole book against its resting orders and vaults:
+    // - the locked totals are exactly what bids hold in quote and asks hold in base
+    // - free plus locked balances plus collected fees are covered by the vaults, which may
+    //   hold more than that when tokens are sent to them directly
+    // - order IDs are unique and below order_counter, and no resting order is empty
+    pub fn check_invariants(&self, book: &Book, base_vault_amount: u64, quote_vault_amount: u64) -> Result<()> {
+        let mut held = Balances::default();
+        let mut order_ids: Vec<u64> = Vec::with_capacity(book.len(Side::Buy) + book.len(Side::Sell));
+        for side in [Side::Buy, Side::Sell] {
+            for order in book.orders(side) {
+                if order.side != side || order.remaining_amount == 0 || order.id >= self.order_counter {
+                    msg!("Invalid resting order {}: side {}, remaining {}", order.id, order.side as u8, order.remaining_amount);
+                    return err!(ErrorCode::InvariantViolation);
                 }
-                
-                return Ok(());
+                let (base_amount, quote_amount) = order.locked_amounts(self.lot_size())?;
+                held.add_locked(base_amount, quote_amount)?;
+                order_ids.push(order.id);
             }
         }
-        
-        // User not found, add new entry if either amount is greater than 0
-        if base_amount > 0 || quote_amount > 0 {
-            require!(self.user_balances.len() < 20, ErrorCode::TooManyUsers);
-            
-            self.user_balances.push(UserBalance {
-                owner: *owner,
-                base_amount,
-                quote_amount,
-            });
+
+        order_ids.sort_unstable();
+        if let Some(pair) = order_ids.windows(2).find(|pair| pair[0] == pair[1]) {
+            msg!("Order ID {} rests more than once", pair[0]);
+            return err!(ErrorCode::InvariantViolation);
+        }
+
+        if held.base_locked != self.totals.base_locked || held.quote_locked != self.totals.quote_locked {
+            msg!("Locked base: {}, quote: {}, but resting orders hold base: {}, quote: {}",
+                self.totals.base_locked, self.totals.quote_locked, held.base_locked, held.quote_locked);
+            return err!(ErrorCode::InvariantViolation);
         }
-        
+
+        self.check_vault_balances(base_vault_amount, quote_vault_amount)
+    }
+
+    // Books created before lot sizes existed read zero for them, and keep pricing per base
+    // atom on a tick of one
+    pub fn lot_size(&self) -> u64 {
+        self.base_lot_size.max(1)
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick_size.max(1)
+    }
+
+    pub fn validate_price(&self, price: u64) -> Result<()> {
+        require!(price.is_multiple_of(self.tick()), ErrorCode::InvalidTickSize);
+        Ok(())
+    }
+
+    pub fn validate_amount(&self, amount: u64) -> Result<()> {
+        require!(amount.is_multiple_of(self.lot_size()), ErrorCode::InvalidLotSize);
+        require!(amount >= self.min_order_size, ErrorCode::OrderTooSmall);
         Ok(())
     }
-    
-    // Get a user's balance
-    pub fn get_balance(&self, owner: &Pubkey) -> (u64, u64) {
-        for balance in &self.user_balances {
-            if balance.owner == *owner {
-                return (balance.base_amount, balance.quote_amount);
+
+    // Maker and taker fee rates for a user with the given traded volume
+    pub fn fee_rates(&self, volume: u64) -> (i16, u16) {
+        self.fee_tiers
+            .iter()
+            .rev()
+            .find(|tier| volume >= tier.min_volume)
+            .map(|tier| (tier.maker_fee_bps, tier.taker_fee_bps))
+            .unwrap_or((self.maker_fee_bps, self.taker_fee_bps))
+    }
+
+    // Rebates are paid out of the taker fee of the same fill, so the largest rebate of any
+    // tier can't exceed the smallest taker fee of any tier
+    pub fn validate_fees(&self) -> Result<()> {
+        require!(self.fee_tiers.len() <= MAX_FEE_TIERS, ErrorCode::InvalidFeeTiers);
+        require!(
+            self.fee_tiers.windows(2).all(|pair| pair[0].min_volume < pair[1].min_volume),
+            ErrorCode::InvalidFeeTiers
+        );
+
+        let rates = self.fee_tiers
+            .iter()
+            .map(|tier| (tier.maker_fee_bps, tier.taker_fee_bps))
+            .chain(std::iter::once((self.maker_fee_bps, self.taker_fee_bps)));
+        let mut max_rebate_bps = 0;
+        let mut min_taker_fee_bps = MAX_FEE_BPS;
+        for (maker_fee_bps, taker_fee_bps) in rates {
+            require!(
+                maker_fee_bps.unsigned_abs() <= MAX_FEE_BPS && taker_fee_bps <= MAX_FEE_BPS,
+                ErrorCode::InvalidFeeRate
+            );
+            if maker_fee_bps < 0 {
+                max_rebate_bps = max_rebate_bps.max(maker_fee_bps.unsigned_abs());
             }
+            min_taker_fee_bps = min_taker_fee_bps.min(taker_fee_bps);
         }
-        (0, 0)
-    }
-    
-    // Subtract from a user's balance
-    pub fn subtract_balance(&mut self, owner: &Pubkey, base_amount: u64, quote_amount: u64) -> Result<()> {
-        for (i, balance) in self.user_balances.iter_mut().enumerate() {
-            if balance.owner == *owner {
-                if base_amount > 0 {
-                    require!(
-                        balance.base_amount >= base_amount,
-                        ErrorCode::InsufficientBalance
-                    );
-                    
-                    balance.base_amount = balance.base_amount
-                        .checked_sub(base_amount)
-                        .ok_or(error!(ErrorCode::CalculationFailure))?;
-                }
-                
-                if quote_amount > 0 {
-                    require!(
-                        balance.quote_amount >= quote_amount,
-                        ErrorCode::InsufficientBalance
-                    );
-                    
-                    balance.quote_amount = balance.quote_amount
-                        .checked_sub(quote_amount)
-                        .ok_or(error!(ErrorCode::CalculationFailure))?;
-                }
-                
-                // Remove the entry if both balances are zero
-                if balance.base_amount == 0 && balance.quote_amount == 0 {
-                    self.user_balances.remove(i);
-                }
-                
-                return Ok(());
-            }
+        require!(max_rebate_bps <= min_taker_fee_bps, ErrorCode::RebateExceedsTakerFee);
+        require!(self.referral_share_bps as u64 <= FEE_BPS_DENOMINATOR, ErrorCode::InvalidFeeRate);
+        Ok(())
+    }
+
+    pub fn validate_market_params(tick_size: u64, base_lot_size: u64, min_order_size: u64) -> Result<()> {
+        require!(tick_size > 0 && base_lot_size > 0, ErrorCode::InvalidMarketParams);
+        require!(min_order_size > 0 && min_order_size.is_multiple_of(base_lot_size), ErrorCode::InvalidMarketParams);
+        Ok(())
+    }
+
+    // Move the orders of a book created before order slabs existed into its order slab.
+    // Inserting them one by one puts them in price-time priority whatever order they were
+    // stored in, as IDs were handed out in the order they were placed. Legacy prices are
+    // per base atom, so they are scaled to the lot size and have to land on the tick grid,
+    // and what remains of each order has to be whole lots. What the orders hold counts
+    // towards the locked totals from here on, so they can be matched before their owners
+    // have open orders accounts.
+    pub fn migrate_legacy_orders(&mut self, book: &mut Book, orders: &[LegacyOrder]) -> Result<()> {
+        for legacy in orders {
+            let mut order: Order = (*legacy).into();
+            order.price = order.price.checked_mul(self.lot_size())
+                .ok_or(ErrorCode::CalculationFailure)?;
+            require!(
+                order.price.is_multiple_of(self.tick()) && order.remaining_amount.is_multiple_of(self.lot_size()),
+                ErrorCode::InvalidMarketParams
+            );
+            let (base_amount, quote_amount) = order.locked_amounts(self.lot_size())?;
+            self.totals.add_locked(base_amount, quote_amount)?;
+            book.insert_order(order)?;
+        }
+        Ok(())
+    }
+
+    // Remove a user's entry from the legacy balances, returning its (base, quote) amounts
+    pub fn take_legacy_balance(&mut self, owner: &Pubkey) -> (u64, u64) {
+        match self.user_balances.iter().position(|balance| balance.owner == *owner) {
+            Some(i) => {
+                let balance = self.user_balances.remove(i);
+                (balance.base_amount, balance.quote_amount)
+            },
+            None => (0, 0),
         }
-        
-        err!(ErrorCode::UserNotFound)
     }
 }
 
@@ -131,12 +242,405 @@ pub struct Order {
     pub original_amount: u64,
     pub remaining_amount: u64,
     pub created_at: i64,
+    pub self_trade_prevention: SelfTradePrevention,
+    // Maker fee rate of the owner's tier when the order was placed, paid on all of its
+    // fills; negative for a rebate. Resting buys lock a positive fee on top of their quote,
+    // so fills never need more than the order holds.
+    pub maker_fee_bps: i16,
+}
+
+impl Order {
+    // Funds held by a resting order as (base, quote): quote for buys, base for sells
+    pub fn locked_amounts(&self, base_lot_size: u64) -> Result<(u64, u64)> {
+        self.locked_amounts_for(self.remaining_amount, base_lot_size)
+    }
+
+    // Funds held for `amount` of this order, at the order's price and maker fee
+    pub fn locked_amounts_for(&self, amount: u64, base_lot_size: u64) -> Result<(u64, u64)> {
+        match self.side {
+            Side::Buy => {
+                let quote_amount = quote_amount(self.price, amount, base_lot_size)?;
+                let fee_reserve = fee_amount(quote_amount, self.maker_fee_bps.max(0) as u16, Rounding::Up)?;
+                let quote_amount = quote_amount.checked_add(fee_reserve)
+                    .ok_or(error!(ErrorCode::CalculationFailure))?;
+                Ok((0, quote_amount))
+            },
+            Side::Sell => Ok((amount, 0)),
+        }
+    }
+
+    // Funds the order stops holding once `amount` of it is filled or cancelled. Taking the
+    // difference between what the remainder holds before and after keeps partial fills from
+    // leaving rounding dust behind.
+    pub fn released_amounts(&self, amount: u64, base_lot_size: u64) -> Result<(u64, u64)> {
+        let remaining_after = self.remaining_amount.checked_sub(amount)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        let (base_before, quote_before) = self.locked_amounts(base_lot_size)?;
+        let (base_after, quote_after) = self.locked_amounts_for(remaining_after, base_lot_size)?;
+        Ok((base_before - base_after, quote_before - quote_after))
+    }
+}
+
+// Layout of orderbook accounts created before order slabs, open orders accounts and fees
+// existed. Resting orders were kept in buys and sells, and balances in user_balances.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacyOrderbook {
+    pub base_asset: Pubkey,
+    pub quote_asset: Pubkey,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub base_decimals: u8,
+    pub quote_decimals: u8,
+    pub buys: Vec<LegacyOrder>,
+    pub sells: Vec<LegacyOrder>,
+    pub authority: Pubkey,
+    pub order_counter: u64,
+    pub user_balances: Vec<UserBalance>,
+    pub bump: u8,
+    pub is_delegated: bool,
 }
 
+impl LegacyOrderbook {
+    // Read an orderbook account in the legacy layout, discriminator included. Whatever
+    // follows the legacy fields is left over from longer order and balance lists.
+    pub fn from_account_data(data: &[u8]) -> Result<LegacyOrderbook> {
+        require!(
+            data.starts_with(Orderbook::DISCRIMINATOR),
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+        let mut body = &data[Orderbook::DISCRIMINATOR.len()..];
+        LegacyOrderbook::deserialize(&mut body)
+            .map_err(|_| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))
+    }
+
+    // The orderbook in the current layout with the given market parameters, and its
+    // resting orders moved into `book`. Legacy balances are kept for their owners to
+    // claim; totals, fees and stats start from nothing but what the orders hold.
+    pub fn migrate(
+        self,
+        book: &mut Book,
+        order_capacity: u16,
+        tick_size: u64,
+        base_lot_size: u64,
+        min_order_size: u64,
+    ) -> Result<Orderbook> {
+        require!(
+            order_capacity > 0
+                && self.buys.len() <= order_capacity as usize
+                && self.sells.len() <= order_capacity as usize,
+            ErrorCode::InvalidOrderCapacity
+        );
+        Orderbook::validate_market_params(tick_size, base_lot_size, min_order_size)?;
+
+        let mut order_book = Orderbook {
+            base_asset: self.base_asset,
+            quote_asset: self.quote_asset,
+            base_vault: self.base_vault,
+            quote_vault: self.quote_vault,
+            base_decimals: self.base_decimals,
+            quote_decimals: self.quote_decimals,
+            authority: self.authority,
+            order_counter: self.order_counter,
+            user_balances: self.user_balances,
+            bump: self.bump,
+            is_delegated: self.is_delegated,
+            order_capacity,
+            totals: Balances::default(),
+            maker_fee_bps: 0,
+            taker_fee_bps: 0,
+            accrued_fees: 0,
+            fee_tiers: Vec::new(),
+            referral_share_bps: 0,
+            tick_size,
+            base_lot_size,
+            min_order_size,
+            last_price: 0,
+            base_volume: 0,
+            quote_volume: 0,
+            trade_count: 0,
+        };
+        order_book.migrate_legacy_orders(book, &self.buys)?;
+        order_book.migrate_legacy_orders(book, &self.sells)?;
+        Ok(order_book)
+    }
+}
 
+// Layout of orders stored in LegacyOrderbook::buys and sells
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct LegacyOrder {
+    pub id: u64,
+    pub owner: Pubkey,
+    pub side: Side,
+    pub price: u64,
+    pub original_amount: u64,
+    pub remaining_amount: u64,
+    pub created_at: i64,
+}
+
+impl From<LegacyOrder> for Order {
+    fn from(order: LegacyOrder) -> Self {
+        Order {
+            id: order.id,
+            owner: order.owner,
+            side: order.side,
+            price: order.price,
+            original_amount: order.original_amount,
+            remaining_amount: order.remaining_amount,
+            created_at: order.created_at,
+            // Legacy orders had no self-trade prevention mode, so they get the default one
+            self_trade_prevention: SelfTradePrevention::CancelTaker,
+            // Legacy orders locked no fee reserve
+            maker_fee_bps: 0,
+        }
+    }
+}
+
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
 #[repr(u8)]
 pub enum Side {
     Buy = 0,
     Sell = 1,
-}
\ No newline at end of file
+}
+
+impl Side {
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TimeInForce {
+    // Rest whatever is not filled immediately
+    GoodTillCancelled = 0,
+    // Fill what is available now and cancel the rest
+    ImmediateOrCancel = 1,
+    // Fill the whole amount now or fail
+    FillOrKill = 2,
+    // Only rest on the book, rejecting the order if it would cross
+    PostOnly = 3,
+    // Only rest on the book, re-pricing one tick away from the best opposite price if it would cross
+    PostOnlySlide = 4,
+}
+
+// What happens when an order would match against another order from the same owner
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+#[repr(u8)]
+pub enum SelfTradePrevention {
+    // Cancel the rest of the incoming order
+    CancelTaker = 0,
+    // Cancel the resting order and keep matching
+    CancelMaker = 1,
+    // Cancel both the resting order and the rest of the incoming order
+    CancelBoth = 2,
+    // Reduce both orders by the overlapping amount, cancelling whichever reaches zero
+    DecrementAndCancel = 3,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::OrderSlab;
+    use crate::state::test_market::Market;
+
+    // A legacy order as the orderbook account stored it before order slabs existed
+    fn legacy_order_bytes(id: u64, owner: Pubkey, side: Side, price: u64, original_amount: u64, remaining_amount: u64) -> Vec<u8> {
+        let mut data = id.to_le_bytes().to_vec();
+        data.extend_from_slice(owner.as_ref());
+        data.push(side as u8);
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&original_amount.to_le_bytes());
+        data.extend_from_slice(&remaining_amount.to_le_bytes());
+        data.extend_from_slice(&(id as i64 * 10).to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn trades_update_the_last_price_and_volumes() {
+        let mut order_book = Market::new(0, 0, 0).order_book;
+        order_book.record_trade(10, 2, 20);
+        order_book.record_trade(12, 1, 12);
+        assert_eq!(order_book.last_price, 12);
+        assert_eq!((order_book.base_volume, order_book.quote_volume), (3, 32));
+        assert_eq!(order_book.trade_count, 2);
+
+        // Lifetime volumes outgrow a u64
+        order_book.record_trade(11, u64::MAX, u64::MAX);
+        assert_eq!(order_book.base_volume, u64::MAX as u128 + 3);
+        assert_eq!(order_book.quote_volume, u64::MAX as u128 + 32);
+    }
+
+    #[test]
+    fn vaults_must_cover_balances_and_fees() {
+        let mut order_book = Market::new(0, 0, 0).order_book;
+        order_book.totals = Balances { base_free: 7, quote_free: 100, base_locked: 3, quote_locked: 50 };
+        order_book.accrued_fees = 4;
+
+        order_book.check_vault_balances(10, 154).unwrap();
+        // Tokens sent straight to a vault are a surplus, not a mismatch
+        order_book.check_vault_balances(11, 200).unwrap();
+        for (base_vault_amount, quote_vault_amount) in [(9, 154), (10, 153)] {
+            let error = order_book.check_vault_balances(base_vault_amount, quote_vault_amount).err().unwrap();
+            assert_eq!(error, ErrorCode::VaultBalanceMismatch.into());
+        }
+    }
+
+    #[test]
+    fn legacy_order_matches_the_stored_layout() {
+        let owner = Pubkey::new_unique();
+        let data = legacy_order_bytes(7, owner, Side::Sell, 15, 5, 3);
+        assert_eq!(data.len(), LegacyOrder::INIT_SPACE);
+
+        let order = LegacyOrder::try_from_slice(&data).unwrap();
+        assert_eq!((order.id, order.owner, order.price), (7, owner, 15));
+        assert!(order.side == Side::Sell);
+        assert_eq!((order.original_amount, order.remaining_amount, order.created_at), (5, 3, 70));
+    }
+
+    // An orderbook account in the legacy layout, allocated for up to 10 orders per side
+    // and 20 user balances. The space its lists don't use holds whatever was written there
+    // before they shrank.
+    fn legacy_orderbook_bytes(authority: Pubkey, buys: &[Vec<u8>], sells: &[Vec<u8>], balances: &[UserBalance]) -> Vec<u8> {
+        let mut data = Orderbook::DISCRIMINATOR.to_vec();
+        for _ in 0..4 {
+            data.extend_from_slice(Pubkey::new_unique().as_ref());
+        }
+        data.extend_from_slice(&[9, 6]);
+        for orders in [buys, sells] {
+            data.extend_from_slice(&(orders.len() as u32).to_le_bytes());
+            data.extend(orders.concat());
+        }
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(&4u64.to_le_bytes());
+        data.extend_from_slice(&(balances.len() as u32).to_le_bytes());
+        for balance in balances {
+            data.extend(balance.try_to_vec().unwrap());
+        }
+        data.extend_from_slice(&[254, 0]);
+        data.resize(8 + 4 * 32 + 2 + 2 * (4 + 10 * 73) + 32 + 8 + (4 + 20 * 48) + 2, 0xab);
+        data
+    }
+
+    #[test]
+    fn migrates_a_book_in_the_legacy_layout() {
+        let (alice, bob, authority) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        // Bids stored out of price order, as books sorted them unstably while matching
+        let buys = [
+            legacy_order_bytes(0, alice, Side::Buy, 10, 4, 4),
+            legacy_order_bytes(2, bob, Side::Buy, 12, 2, 2),
+            legacy_order_bytes(3, bob, Side::Buy, 10, 1, 1),
+        ];
+        let sells = [legacy_order_bytes(1, alice, Side::Sell, 15, 5, 3)];
+        let balance = UserBalance { owner: bob, base_amount: 7, quote_amount: 70 };
+        let data = legacy_orderbook_bytes(authority, &buys, &sells, &[balance]);
+
+        // The current layout would read its new fields from the stale tail, which here
+        // makes for a fee tier list longer than the account
+        assert!(Orderbook::try_deserialize(&mut &data[..]).is_err());
+
+        let legacy = LegacyOrderbook::from_account_data(&data).unwrap();
+        assert_eq!((legacy.authority, legacy.order_counter, legacy.bump), (authority, 4, 254));
+        assert_eq!((legacy.buys.len(), legacy.sells.len(), legacy.user_balances.len()), (3, 1, 1));
+
+        let mut slab = vec![0u64; OrderSlab::space(3).div_ceil(8)];
+        let mut book = Book::load(bytemuck::cast_slice_mut(&mut slab)).unwrap();
+        book.initialize(Pubkey::new_unique(), 0);
+        let order_book = legacy.migrate(&mut book, 3, 1, 1, 1).unwrap();
+
+        // Written back in the current layout, every new field reads what migration set
+        let mut account = vec![0; 8 + Orderbook::INIT_SPACE];
+        order_book.try_serialize(&mut &mut account[..]).unwrap();
+        let order_book = Orderbook::try_deserialize(&mut &account[..]).unwrap();
+        assert_eq!((order_book.authority, order_book.order_counter, order_book.bump), (authority, 4, 254));
+        assert_eq!(order_book.order_capacity, 3);
+        assert_eq!((order_book.tick_size, order_book.base_lot_size, order_book.min_order_size), (1, 1, 1));
+        assert_eq!((order_book.maker_fee_bps, order_book.taker_fee_bps, order_book.referral_share_bps), (0, 0, 0));
+        assert_eq!(order_book.accrued_fees, 0);
+        assert!(order_book.fee_tiers.is_empty());
+        assert_eq!((order_book.last_price, order_book.base_volume, order_book.trade_count), (0, 0, 0));
+        // What the orders hold is locked in the totals even before their owners claim it
+        assert_eq!((order_book.totals.base_free, order_book.totals.quote_free), (0, 0));
+        assert_eq!((order_book.totals.base_locked, order_book.totals.quote_locked), (3, 74));
+        assert_eq!(order_book.user_balances[0].quote_amount, 70);
+
+        let bids: Vec<(u64, u64)> = book.orders(Side::Buy).map(|order| (order.id, order.price)).collect();
+        assert_eq!(bids, vec![(2, 12), (0, 10), (3, 10)]);
+        let ask = book.order_at(Side::Sell, 0);
+        assert_eq!((ask.id, ask.owner, ask.remaining_amount, ask.maker_fee_bps), (1, alice, 3, 0));
+        assert!(ask.self_trade_prevention == SelfTradePrevention::CancelTaker);
+    }
+
+    #[test]
+    fn migration_checks_the_market_params_against_the_orders() {
+        let owner = Pubkey::new_unique();
+        let buys: Vec<Vec<u8>> = (0..10).map(|id| legacy_order_bytes(id, owner, Side::Buy, 10, 4, 4)).collect();
+        // A book whose bids were full
+        let data = legacy_orderbook_bytes(Pubkey::new_unique(), &buys, &[], &[]);
+
+        let migrate = |order_capacity: u16, tick_size: u64, base_lot_size: u64| {
+            let mut slab = vec![0u64; OrderSlab::space(10).div_ceil(8)];
+            let mut book = Book::load(bytemuck::cast_slice_mut(&mut slab)).unwrap();
+            book.initialize(Pubkey::new_unique(), 0);
+            LegacyOrderbook::from_account_data(&data).unwrap().migrate(&mut book, order_capacity, tick_size, base_lot_size, base_lot_size)
+        };
+        assert_eq!(migrate(9, 1, 1).err().unwrap(), ErrorCode::InvalidOrderCapacity.into());
+        // Prices per atom become prices per lot, which have to land on the tick grid
+        let order_book = migrate(10, 20, 2).unwrap();
+        assert_eq!(order_book.totals.quote_locked, 400);
+        assert_eq!(migrate(10, 30, 2).err().unwrap(), ErrorCode::InvalidMarketParams.into());
+        // Remaining amounts have to be whole lots
+        assert_eq!(migrate(10, 1, 8).err().unwrap(), ErrorCode::InvalidMarketParams.into());
+        assert_eq!(migrate(10, 0, 1).err().unwrap(), ErrorCode::InvalidMarketParams.into());
+    }
+
+    #[test]
+    fn invariants_catch_locked_totals_that_orders_do_not_hold() {
+        let mut market = Market::new(0, 0, 1);
+        market.deposit(0, 0, 1_000);
+        market.rest(0, Side::Buy, 10, 5);
+
+        // Drop the lock from the totals while the order still rests
+        market.order_book.totals.unlock(0, 50).unwrap();
+        let book = Book::load(bytemuck::cast_slice_mut(&mut market.slab)).unwrap();
+        let error = market.order_book.check_invariants(&book, 0, 1_000).err().unwrap();
+        assert_eq!(error, ErrorCode::InvariantViolation.into());
+    }
+
+    #[test]
+    fn invariants_catch_vault_shortfall() {
+        let mut market = Market::new(0, 0, 1);
+        market.deposit(0, 10, 1_000);
+        market.rest(0, Side::Sell, 10, 5);
+
+        let book = Book::load(bytemuck::cast_slice_mut(&mut market.slab)).unwrap();
+        // Tokens sent straight to a vault are a surplus, not a violation
+        market.order_book.check_invariants(&book, 11, 1_500).unwrap();
+        let error = market.order_book.check_invariants(&book, 9, 1_000).err().unwrap();
+        assert_eq!(error, ErrorCode::VaultBalanceMismatch.into());
+    }
+
+    #[test]
+    fn invariants_catch_bad_resting_orders() {
+        let mut market = Market::new(0, 0, 1);
+        market.deposit(0, 0, 1_000);
+        let order_id = market.rest(0, Side::Buy, 10, 5);
+
+        // An ID the orderbook hasn't handed out yet
+        market.order_book.order_counter = order_id;
+        let book = Book::load(bytemuck::cast_slice_mut(&mut market.slab)).unwrap();
+        let error = market.order_book.check_invariants(&book, 0, 1_000).err().unwrap();
+        assert_eq!(error, ErrorCode::InvariantViolation.into());
+
+        // The same ID resting twice
+        market.order_book.order_counter = order_id + 1;
+        let mut book = Book::load(bytemuck::cast_slice_mut(&mut market.slab)).unwrap();
+        let order = book.order_at(Side::Buy, 0);
+        book.insert_order(order).unwrap();
+        let error = market.order_book.check_invariants(&book, 0, 1_000).err().unwrap();
+        assert_eq!(error, ErrorCode::InvariantViolation.into());
+    }
+}
@@ -0,0 +1,237 @@
+// Test harness driving an orderbook through the same functions as its instructions
+
+use anchor_lang::prelude::*;
+
+use crate::constants::{EVENT_QUEUE_CAPACITY, FEE_VOLUME_DAYS};
+use crate::instructions::{
+    cancel_resting_order, place_limit_order, place_market_order, rematch_order, MarketOrderResult, MatchingResult,
+};
+use crate::state::{
+    Balances, Book, Candles, EventQueue, MarketHistory, OpenOrders, Order, Orderbook, OrderSlab,
+    SelfTradePrevention, Side, TakerFunds, TimeInForce, TradeHistory, Traders,
+};
+
+pub const CAPACITY: u16 = 64;
+
+// An orderbook, its order slab, event queue, trade history and candles and the open
+// orders accounts of its users, driven the way the instructions drive them at the
+// time on `clock`. Unless auto_consume is turned off, the queue is cranked after every
+// match. Tracks what was deposited to check conservation.
+pub struct Market {
+    pub order_book: Orderbook,
+    pub slab: Vec<u64>,
+    pub event_queue: Box<EventQueue>,
+    pub trade_history: Box<TradeHistory>,
+    pub candles: Box<Candles>,
+    pub clock: Clock,
+    pub auto_consume: bool,
+    pub users: Vec<OpenOrders>,
+    pub base_deposited: u64,
+    pub quote_deposited: u64,
+}
+
+impl Market {
+    pub fn new(maker_fee_bps: i16, taker_fee_bps: u16, user_count: usize) -> Market {
+        let order_book = Orderbook {
+            base_asset: Pubkey::new_unique(),
+            quote_asset: Pubkey::new_unique(),
+            base_vault: Pubkey::new_unique(),
+            quote_vault: Pubkey::new_unique(),
+            base_decimals: 0,
+            quote_decimals: 0,
+            authority: Pubkey::new_unique(),
+            order_counter: 0,
+            user_balances: Vec::new(),
+            bump: 0,
+            is_delegated: false,
+            order_capacity: CAPACITY,
+            totals: Balances::default(),
+            maker_fee_bps,
+            taker_fee_bps,
+            accrued_fees: 0,
+            fee_tiers: Vec::new(),
+            referral_share_bps: 0,
+            tick_size: 1,
+            base_lot_size: 1,
+            min_order_size: 1,
+            last_price: 0,
+            base_volume: 0,
+            quote_volume: 0,
+            trade_count: 0,
+        };
+
+        // Order slab account data, held in u64s so the slots are aligned as on chain
+        let mut slab = vec![0; OrderSlab::space(CAPACITY).div_ceil(8)];
+        Book::load(bytemuck::cast_slice_mut(&mut slab)).unwrap().initialize(order_book.authority, 0);
+
+        // Tests have no orderbook address, so the book's unique authority stands in for it
+        let users = (0..user_count)
+            .map(|_| OpenOrders {
+                order_book: order_book.authority,
+                owner: Pubkey::new_unique(),
+                balances: Balances::default(),
+                order_ids: Vec::new(),
+                bump: 0,
+                daily_volume: [0; FEE_VOLUME_DAYS],
+                volume_day: 0,
+            })
+            .collect();
+
+        let mut event_queue: Box<EventQueue> = Box::new(bytemuck::Zeroable::zeroed());
+        event_queue.initialize(order_book.authority, 0);
+        let mut trade_history: Box<TradeHistory> = Box::new(bytemuck::Zeroable::zeroed());
+        trade_history.initialize(order_book.authority, 0);
+        let mut candles: Box<Candles> = Box::new(bytemuck::Zeroable::zeroed());
+        candles.initialize(order_book.authority, 0);
+
+        Market {
+            order_book,
+            slab,
+            event_queue,
+            trade_history,
+            candles,
+            clock: Clock::default(),
+            auto_consume: true,
+            users,
+            base_deposited: 0,
+            quote_deposited: 0,
+        }
+    }
+
+    pub fn user(&self, user: usize) -> OpenOrders {
+        self.users[user].clone()
+    }
+
+    // Write an open orders account back, as Anchor does when an instruction exits
+    pub fn save(&mut self, user: usize, open_orders: &OpenOrders) {
+        self.users[user] = open_orders.clone();
+    }
+
+    pub fn book(&mut self) -> Book<'_> {
+        Book::load(bytemuck::cast_slice_mut(&mut self.slab)).unwrap()
+    }
+
+    // An order carrying the maker fee of its owner's current tier
+    pub fn new_order(&mut self, open_orders: &OpenOrders, side: Side, price: u64, amount: u64) -> Order {
+        Order {
+            id: self.order_book.next_order_id(),
+            owner: open_orders.owner,
+            side,
+            price,
+            original_amount: amount,
+            remaining_amount: amount,
+            created_at: 0,
+            self_trade_prevention: SelfTradePrevention::CancelTaker,
+            maker_fee_bps: self.order_book.fee_rates(open_orders.current_volume(0)).0,
+        }
+    }
+
+    pub fn deposit(&mut self, user: usize, base_amount: u64, quote_amount: u64) {
+        let mut open_orders = self.user(user);
+        open_orders.deposit(&mut self.order_book, base_amount, quote_amount).unwrap();
+        self.save(user, &open_orders);
+        self.base_deposited += base_amount;
+        self.quote_deposited += quote_amount;
+    }
+
+    // Lock and rest an order without matching it, to set up a crossed book
+    pub fn rest(&mut self, user: usize, side: Side, price: u64, amount: u64) -> u64 {
+        let mut open_orders = self.user(user);
+        let order = self.new_order(&open_orders, side, price, amount);
+        let (base_amount, quote_amount) = order.locked_amounts(self.order_book.lot_size()).unwrap();
+        open_orders.lock(&mut self.order_book, base_amount, quote_amount).unwrap();
+        open_orders.track_order(order.id).unwrap();
+        self.book().insert_order(order).unwrap();
+        self.save(user, &open_orders);
+        order.id
+    }
+
+    // Place a limit order as create_order does, returning it with its remaining amount
+    pub fn limit(&mut self, user: usize, side: Side, price: u64, amount: u64, time_in_force: TimeInForce) -> Result<MatchingResult> {
+        let mut open_orders = self.user(user);
+        let mut order = self.new_order(&open_orders, side, price, amount);
+        let mut book = Book::load(bytemuck::cast_slice_mut(&mut self.slab))?;
+        let mut traders = Traders::load(&mut open_orders, &mut self.order_book, None, &mut self.event_queue, MarketHistory { trades: &mut self.trade_history, candles: &mut self.candles }, TakerFunds::Locked, &self.clock)?;
+        let result = place_limit_order(&mut book, &mut traders, &mut order, time_in_force)?;
+        self.save(user, &open_orders);
+        self.crank();
+        Ok(result)
+    }
+
+    // Place a market order as create_market_order does
+    pub fn market(&mut self, user: usize, side: Side, amount: u64, worst_price: u64, quote_budget: u64) -> Result<MarketOrderResult> {
+        let mut open_orders = self.user(user);
+        let mut order = self.new_order(&open_orders, side, worst_price, amount);
+        let mut book = Book::load(bytemuck::cast_slice_mut(&mut self.slab))?;
+        let mut traders = Traders::load(&mut open_orders, &mut self.order_book, None, &mut self.event_queue, MarketHistory { trades: &mut self.trade_history, candles: &mut self.candles }, TakerFunds::Free, &self.clock)?;
+        let result = place_market_order(&mut book, &mut traders, &mut order, quote_budget)?;
+        self.save(user, &open_orders);
+        self.crank();
+        Ok(result)
+    }
+
+    // Match a resting order again as match_order does
+    pub fn rematch(&mut self, user: usize, order_id: u64) -> Order {
+        let mut open_orders = self.user(user);
+        let owner = open_orders.owner;
+        let mut book = Book::load(bytemuck::cast_slice_mut(&mut self.slab)).unwrap();
+        let mut traders = Traders::load(&mut open_orders, &mut self.order_book, None, &mut self.event_queue, MarketHistory { trades: &mut self.trade_history, candles: &mut self.candles }, TakerFunds::Locked, &self.clock).unwrap();
+        let order = rematch_order(&mut book, &mut traders, order_id, &owner).unwrap();
+        self.save(user, &open_orders);
+        self.crank();
+        order
+    }
+
+    // Consume up to `limit` events as consume_events does, with every user's account passed
+    pub fn consume_events(&mut self, limit: usize) -> usize {
+        let mut makers: Vec<&mut OpenOrders> = self.users.iter_mut().collect();
+        self.event_queue.consume(&mut makers, limit).unwrap()
+    }
+
+    pub fn crank(&mut self) {
+        if self.auto_consume {
+            self.consume_events(EVENT_QUEUE_CAPACITY);
+        }
+    }
+
+    // Cancel a resting order as cancel_order does
+    pub fn cancel(&mut self, user: usize, order_id: u64) {
+        let mut book = Book::load(bytemuck::cast_slice_mut(&mut self.slab)).unwrap();
+        cancel_resting_order(&mut book, &mut self.order_book, &mut self.users[user], order_id).unwrap();
+    }
+
+    // Every token deposited is in a user's balance or in collected fees, the orderbook's
+    // totals match the users' balances, and every user's locked funds are exactly what
+    // their resting orders hold
+    pub fn assert_conserved(&mut self) {
+        let lot_size = self.order_book.lot_size();
+        let users = &self.users;
+        let mut sum = Balances::default();
+        for user in users {
+            sum.credit(user.balances.base_free, user.balances.quote_free).unwrap();
+            sum.add_locked(user.balances.base_locked, user.balances.quote_locked).unwrap();
+        }
+        assert_eq!(sum.base_free, self.order_book.totals.base_free);
+        assert_eq!(sum.quote_free, self.order_book.totals.quote_free);
+        assert_eq!(sum.base_locked, self.order_book.totals.base_locked);
+        assert_eq!(sum.quote_locked, self.order_book.totals.quote_locked);
+
+        let (base_amount, quote_amount) = self.order_book.totals.total().unwrap();
+        assert_eq!(base_amount, self.base_deposited);
+        assert_eq!(quote_amount + self.order_book.accrued_fees, self.quote_deposited);
+
+        let book = Book::load(bytemuck::cast_slice_mut(&mut self.slab)).unwrap();
+        self.order_book.check_invariants(&book, self.base_deposited, self.quote_deposited).unwrap();
+        for user in users {
+            let mut held = Balances::default();
+            for order in book.orders(Side::Buy).chain(book.orders(Side::Sell)) {
+                if order.owner == user.owner {
+                    let (base_amount, quote_amount) = order.locked_amounts(lot_size).unwrap();
+                    held.add_locked(base_amount, quote_amount).unwrap();
+                    assert!(user.order_ids.contains(&order.id));
+                }
+            }
+            assert_eq!((held.base_locked, held.quote_locked), (user.balances.base_locked, user.balances.quote_locked));
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::mem::size_of;
+
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+
+use crate::constants::TRADE_HISTORY_CAPACITY;
+use crate::error::ErrorCode;
+use crate::state::Side;
+
+// A fill, at the resting order's price
+#[zero_copy]
+pub struct Trade {
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub price: u64,
+    pub amount: u64,
+    pub slot: u64,
+    pub timestamp: i64,
+    pub taker_side: u8,
+    pub padding: [u8; 7],
+}
+
+// The most recent fills of an orderbook, so clients can show them without an indexer.
+// Once full, each new trade overwrites the oldest one.
+#[account(zero_copy)]
+pub struct TradeHistory {
+    pub order_book: Pubkey,
+    // Slot the next trade is written to
+    pub head: u32,
+    pub count: u32,
+    // Number of trades ever recorded
+    pub trade_count: u64,
+    pub bump: u8,
+    pub padding: [u8; 7],
+    pub trades: [Trade; TRADE_HISTORY_CAPACITY],
+}
+
+impl Trade {
+    pub fn taker_side(&self) -> Side {
+        match self.taker_side {
+            0 => Side::Buy,
+            _ => Side::Sell,
+        }
+    }
+}
+
+impl TradeHistory {
+    pub fn initialize(&mut self, order_book: Pubkey, bump: u8) {
+        self.order_book = order_book;
+        self.head = 0;
+        self.count = 0;
+        self.trade_count = 0;
+        self.bump = bump;
+    }
+
+    pub fn record(&mut self, trade: Trade) {
+        self.trades[self.head as usize] = trade;
+        self.head = ((self.head as usize + 1) % TRADE_HISTORY_CAPACITY) as u32;
+        self.count = (self.count + 1).min(TRADE_HISTORY_CAPACITY as u32);
+        self.trade_count += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    // Recorded trades, most recent first
+    pub fn recent(&self) -> impl Iterator<Item = &Trade> {
+        (1..=self.len()).map(move |age| {
+            &self.trades[(self.head as usize + TRADE_HISTORY_CAPACITY - age) % TRADE_HISTORY_CAPACITY]
+        })
+    }
+
+    // Read a trade history from its account data as fetched over RPC, discriminator
+    // included. The data needn't be aligned.
+    pub fn deserialize(data: &[u8]) -> Result<TradeHistory> {
+        let discriminator = TradeHistory::DISCRIMINATOR;
+        require!(data.starts_with(discriminator), ErrorCode::InvalidTradeHistory);
+        let body = data.get(discriminator.len()..discriminator.len() + size_of::<TradeHistory>())
+            .ok_or(ErrorCode::InvalidTradeHistory)?;
+        Ok(bytemuck::pod_read_unaligned(body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(price: u64) -> Trade {
+        Trade {
+            maker: Pubkey::default(),
+            taker: Pubkey::default(),
+            price,
+            amount: 1,
+            slot: price,
+            timestamp: 0,
+            taker_side: Side::Sell as u8,
+            padding: [0; 7],
+        }
+    }
+
+    fn history() -> Box<TradeHistory> {
+        let mut history: Box<TradeHistory> = Box::new(bytemuck::Zeroable::zeroed());
+        history.initialize(Pubkey::new_unique(), 0);
+        history
+    }
+
+    #[test]
+    fn lists_trades_most_recent_first() {
+        let mut history = history();
+        assert!(history.is_empty());
+        assert_eq!(history.recent().count(), 0);
+
+        for price in [10, 12, 11] {
+            history.record(trade(price));
+        }
+        assert_eq!((history.len(), history.trade_count, history.head), (3, 3, 3));
+        let prices: Vec<u64> = history.recent().map(|trade| trade.price).collect();
+        assert_eq!(prices, vec![11, 12, 10]);
+    }
+
+    #[test]
+    fn overwrites_the_oldest_trades_once_full() {
+        let mut history = history();
+        let trade_count = TRADE_HISTORY_CAPACITY as u64 + 5;
+        for price in 1..=trade_count {
+            history.record(trade(price));
+        }
+        assert_eq!(history.len(), TRADE_HISTORY_CAPACITY);
+        assert_eq!(history.trade_count, trade_count);
+        assert_eq!(history.head, 5);
+
+        let prices: Vec<u64> = history.recent().map(|trade| trade.price).collect();
+        let expected: Vec<u64> = (6..=trade_count).rev().collect();
+        assert_eq!(prices, expected);
+    }
+
+    #[test]
+    fn reads_the_account_data_as_fetched() {
+        let mut history = history();
+        history.record(trade(10));
+        history.record(trade(12));
+
+        // Clients read the account data as fetched, discriminator and all
+        let mut data = TradeHistory::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(bytemuck::bytes_of(&*history));
+        let read = TradeHistory::deserialize(&data).unwrap();
+        let prices: Vec<u64> = read.recent().map(|trade| trade.price).collect();
+        assert_eq!(prices, vec![12, 10]);
+        assert_eq!(read.order_book, history.order_book);
+        assert!(TradeHistory::deserialize(&data[1..]).is_err());
+        assert!(TradeHistory::deserialize(&data[..data.len() - 1]).is_err());
+    }
+}
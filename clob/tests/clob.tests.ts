@@ -2,12 +2,18 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Clob } from "../target/types/clob";
 import { LAMPORTS_PER_SOL } from "@solana/web3.js";
-import { createMint, TOKEN_PROGRAM_ID, getOrCreateAssociatedTokenAccount, mintTo } from "@solana/spl-token";
+import { createMint, TOKEN_PROGRAM_ID, getAccount, getAssociatedTokenAddressSync, getOrCreateAssociatedTokenAccount, mintTo } from "@solana/spl-token";
+import { assert } from "chai";
 import {
   GetCommitmentSignature,
 } from "@magicblock-labs/ephemeral-rollups-sdk";
 
 const SEED_ORDERBOOK = "orderbook"; // Use the correct seed for your orderbook PDA
+const SEED_ORDER_SLAB = "order_slab";
+const SEED_EVENT_QUEUE = "event_queue";
+const SEED_TRADE_HISTORY = "trade_history";
+const SEED_CANDLES = "candles";
+const SEED_OPEN_ORDERS = "open_orders";
 
 describe("clob", () => {
   // Configure the client to use the local cluster.
@@ -43,13 +49,22 @@ describe("clob", () => {
 
   // Example: Derive the orderbook PDA (update seeds as needed)
   let orderbookPda: anchor.web3.PublicKey;
+  let orderSlabPda: anchor.web3.PublicKey;
+  let eventQueuePda: anchor.web3.PublicKey;
+  let tradeHistoryPda: anchor.web3.PublicKey;
+  let candlesPda: anchor.web3.PublicKey;
+  let openOrdersPda: anchor.web3.PublicKey;
+  let takerOpenOrdersPda: anchor.web3.PublicKey;
   let baseTokenMint: anchor.web3.PublicKey;
   let quoteTokenMint: anchor.web3.PublicKey;
   let user: anchor.web3.Keypair;
+  // A second trader who takes the user's resting orders
+  let taker: anchor.web3.Keypair;
 
   before(async () => {
     // Generate test accounts and mints as needed
     user = anchor.web3.Keypair.generate();
+    taker = anchor.web3.Keypair.generate();
 
     // Generate keypairs for the mints
     const baseTokenMintKeypair = anchor.web3.Keypair.generate();
@@ -90,6 +105,45 @@ describe("clob", () => {
     );
     console.log("Orderbook PDA: ", orderbookPda.toString());
 
+    // The resting orders live in a separate zero-copy account owned by the orderbook
+    [orderSlabPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from(SEED_ORDER_SLAB), orderbookPda.toBuffer()],
+      program.programId
+    );
+    console.log("Order slab PDA: ", orderSlabPda.toString());
+
+    // Makers' sides of fills wait in the event queue until consume_events settles them
+    [eventQueuePda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from(SEED_EVENT_QUEUE), orderbookPda.toBuffer()],
+      program.programId
+    );
+    console.log("Event queue PDA: ", eventQueuePda.toString());
+
+    // The most recent fills are kept on chain for the UI
+    [tradeHistoryPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from(SEED_TRADE_HISTORY), orderbookPda.toBuffer()],
+      program.programId
+    );
+    console.log("Trade history PDA: ", tradeHistoryPda.toString());
+
+    // 1m, 1h and 1d candles, updated on every fill
+    [candlesPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from(SEED_CANDLES), orderbookPda.toBuffer()],
+      program.programId
+    );
+    console.log("Candles PDA: ", candlesPda.toString());
+
+    // Each user keeps their balances and order IDs in their own open orders account
+    [openOrdersPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from(SEED_OPEN_ORDERS), orderbookPda.toBuffer(), user.publicKey.toBuffer()],
+      program.programId
+    );
+    console.log("Open orders PDA: ", openOrdersPda.toString());
+    [takerOpenOrdersPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from(SEED_OPEN_ORDERS), orderbookPda.toBuffer(), taker.publicKey.toBuffer()],
+      program.programId
+    );
+
     // Create or get the user's base token account
     const userBaseAccount = await getOrCreateAssociatedTokenAccount(
       provider.connection,
@@ -125,6 +179,28 @@ describe("clob", () => {
       provider.wallet.payer, // authority
       1_000_000_000 // amount, adjust as needed
     );
+
+    // The taker only needs base tokens to sell
+    const takerBaseAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      provider.wallet.payer,
+      baseTokenMint,
+      taker.publicKey
+    );
+    await mintTo(
+      provider.connection,
+      provider.wallet.payer,
+      baseTokenMint,
+      takerBaseAccount.address,
+      provider.wallet.payer,
+      1_000_000
+    );
+    await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      provider.wallet.payer,
+      quoteTokenMint,
+      taker.publicKey
+    );
   });
 
   it("Initialize orderbook", async () => {
@@ -143,8 +219,12 @@ describe("clob", () => {
     console.log("associatedTokenProgram: ", anchor.utils.token.ASSOCIATED_PROGRAM_ID.toString());
     console.log("tokenProgram: ", TOKEN_PROGRAM_ID.toString());
     console.log("systemProgram: ", anchor.web3.SystemProgram.programId.toString());
+    const orderCapacity = 20; // resting orders per side
+    const tickSize = new anchor.BN(1); // prices are quote atoms per base lot
+    const baseLotSize = new anchor.BN(1);
+    const minOrderSize = new anchor.BN(1);
     const tx = await program.methods
-      .initializeOrderbook()
+      .initializeOrderbook(orderCapacity, tickSize, baseLotSize, minOrderSize)
       .accounts({
         payer: provider.wallet.publicKey,
         baseTokenMint,
@@ -155,6 +235,14 @@ describe("clob", () => {
         quoteVault,
         // @ts-ignore
         orderBook: orderbookPda,
+        // @ts-ignore
+        orderSlab: orderSlabPda,
+        // @ts-ignore
+        eventQueue: eventQueuePda,
+        // @ts-ignore
+        tradeHistory: tradeHistoryPda,
+        // @ts-ignore
+        candles: candlesPda,
          // @ts-ignore
          associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
         // @ts-ignore
@@ -166,8 +254,93 @@ describe("clob", () => {
     console.log("Initialize orderbook tx:", tx);
   });
 
+  it("Resize orderbook", async () => {
+    const orderCapacity = 40;
+    const tx = await program.methods
+      .resizeOrderbook(orderCapacity)
+      .accounts({
+        authority: provider.wallet.publicKey,
+        baseTokenMint,
+        quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+        // @ts-ignore
+        orderSlab: orderSlabPda,
+        // @ts-ignore
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+    console.log("Resize orderbook tx:", tx);
+  });
+
+  it("Set fees", async () => {
+    // 2 bps for makers and 5 bps for takers, charged in quote on every fill, with 20% of
+    // the taker fee going to the referrer passed with an order
+    const tx = await program.methods
+      .setFees(2, 5, 2_000)
+      .accounts({
+        authority: provider.wallet.publicKey,
+        baseTokenMint,
+        quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+      })
+      .rpc();
+    console.log("Set fees tx:", tx);
+  });
+
+  it("Set fee tiers", async () => {
+    // Users with more traded quote volume pay less, and the top tier earns a maker rebate
+    const feeTiers = [
+      { minVolume: new anchor.BN(1_000_000), makerFeeBps: 1, takerFeeBps: 4 },
+      { minVolume: new anchor.BN(10_000_000), makerFeeBps: -1, takerFeeBps: 3 },
+    ];
+    const tx = await program.methods
+      .setFeeTiers(feeTiers)
+      .accounts({
+        authority: provider.wallet.publicKey,
+        baseTokenMint,
+        quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+      })
+      .rpc();
+    console.log("Set fee tiers tx:", tx);
+  });
+
+  it("Create open orders", async () => {
+    for (const [owner, openOrders] of [[user.publicKey, openOrdersPda], [taker.publicKey, takerOpenOrdersPda]]) {
+      const tx = await program.methods
+        .createOpenOrders()
+        .accounts({
+          payer: provider.wallet.publicKey,
+          owner,
+          baseTokenMint,
+          quoteTokenMint,
+          // @ts-ignore
+          orderBook: orderbookPda,
+          // @ts-ignore
+          orderSlab: orderSlabPda,
+          // @ts-ignore
+          eventQueue: eventQueuePda,
+          // @ts-ignore
+          openOrders,
+          // @ts-ignore
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+      console.log("Create open orders tx:", tx);
+
+      const account = await program.account.openOrders.fetch(openOrders);
+      assert.ok(account.owner.equals(owner));
+      assert.equal(account.balances.baseFree.toNumber(), 0);
+      assert.equal(account.balances.quoteFree.toNumber(), 0);
+    }
+  });
+
   it("Deposit balance", async () => {
-    const quoteAmount = new anchor.BN(1000);
+    // Enough quote for the buy below with its fee reserve
+    const quoteAmount = new anchor.BN(2000);
     const baseAmount = new anchor.BN(500);
 
     const userBaseAccount = anchor.utils.token.associatedAddress({
@@ -210,6 +383,8 @@ describe("clob", () => {
          // @ts-ignore
         orderBook: orderbookPda,
         // @ts-ignore
+        openOrders: openOrdersPda,
+        // @ts-ignore
         userBaseAccount,
         // @ts-ignore
         userQuoteAccount,
@@ -225,11 +400,49 @@ describe("clob", () => {
       .signers([user])
       .rpc();
     console.log("Deposit balance tx:", tx);
+
+    // The taker deposits base only
+    await program.methods
+      .depositBalance(new anchor.BN(0), new anchor.BN(100))
+      .accounts({
+        user: taker.publicKey,
+        baseTokenMint,
+        quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+        // @ts-ignore
+        openOrders: takerOpenOrdersPda,
+        // @ts-ignore
+        userBaseAccount: getAssociatedTokenAddressSync(baseTokenMint, taker.publicKey),
+        // @ts-ignore
+        userQuoteAccount: getAssociatedTokenAddressSync(quoteTokenMint, taker.publicKey),
+        // @ts-ignore
+        baseVault,
+        // @ts-ignore
+        quoteVault,
+        // @ts-ignore
+        associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([taker])
+      .rpc();
+
+    const openOrders = await program.account.openOrders.fetch(openOrdersPda);
+    assert.equal(openOrders.balances.baseFree.toNumber(), 500);
+    assert.equal(openOrders.balances.quoteFree.toNumber(), 2000);
+    const takerOpenOrders = await program.account.openOrders.fetch(takerOpenOrdersPda);
+    assert.equal(takerOpenOrders.balances.baseFree.toNumber(), 100);
+    const orderBook = await program.account.orderbook.fetch(orderbookPda);
+    assert.equal(orderBook.totals.baseFree.toNumber(), 600);
+    assert.equal(orderBook.totals.quoteFree.toNumber(), 2000);
   });
 
   it("Create order on Solana", async () => {
     const start = Date.now();
     const side = 0; // 0 = buy, 1 = sell
+    const timeInForce = 0; // 0 = GTC, 1 = IOC, 2 = FOK, 3 = post-only, 4 = post-only slide
+    const selfTradePrevention = 0; // 0 = cancel taker, 1 = cancel maker, 2 = cancel both, 3 = decrement and cancel
     const price = new anchor.BN(10);
     const amount = new anchor.BN(100);
     
@@ -243,7 +456,7 @@ describe("clob", () => {
     console.log("orderbookPda: ", orderbookPda.toString());
     console.log("--------------------------------");
     const txHash = await program.methods
-      .createOrder(side, price, amount)
+      .createOrder(side, timeInForce, selfTradePrevention, price, amount)
       .accounts({
         user: user.publicKey,
         baseTokenMint,
@@ -251,14 +464,321 @@ describe("clob", () => {
         // @ts-ignore
         orderBook: orderbookPda,
         // @ts-ignore
+        orderSlab: orderSlabPda,
+        // @ts-ignore
+        eventQueue: eventQueuePda,
+        // @ts-ignore
+        tradeHistory: tradeHistoryPda,
+        // @ts-ignore
+        candles: candlesPda,
+        // @ts-ignore
+        openOrders: openOrdersPda,
+        referrerOpenOrders: null, // no referrer
+        // @ts-ignore
         systemProgram: anchor.web3.SystemProgram.programId,
       })
       .signers([user])
       .rpc();
     const duration = Date.now() - start;
     console.log(`${duration}ms (Base Layer) Create Order txHash: ${txHash}`);
+
+    // 1_000 quote at the limit price plus a 1 atom maker fee reserve stays locked
+    const openOrders = await program.account.openOrders.fetch(openOrdersPda);
+    assert.equal(openOrders.balances.quoteLocked.toNumber(), 1001);
+    assert.equal(openOrders.balances.quoteFree.toNumber(), 999);
+    assert.equal(openOrders.orderIds.length, 1);
   });
-  
+
+  it("Cancel order", async () => {
+    const side = 1; // 0 = buy, 1 = sell
+    const timeInForce = 0; // 0 = GTC, 1 = IOC, 2 = FOK, 3 = post-only, 4 = post-only slide
+    const selfTradePrevention = 0; // 0 = cancel taker, 1 = cancel maker, 2 = cancel both, 3 = decrement and cancel
+    const price = new anchor.BN(15);
+    const amount = new anchor.BN(10);
+
+    await program.methods
+      .createOrder(side, timeInForce, selfTradePrevention, price, amount)
+      .accounts({
+        user: user.publicKey,
+        baseTokenMint,
+        quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+        // @ts-ignore
+        orderSlab: orderSlabPda,
+        // @ts-ignore
+        eventQueue: eventQueuePda,
+        // @ts-ignore
+        tradeHistory: tradeHistoryPda,
+        // @ts-ignore
+        candles: candlesPda,
+        // @ts-ignore
+        openOrders: openOrdersPda,
+        referrerOpenOrders: null, // no referrer
+        // @ts-ignore
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    const orderBook = await program.account.orderbook.fetch(orderbookPda);
+    const orderId = orderBook.orderCounter.subn(1);
+    console.log("Cancelling order ID: ", orderId.toString());
+    let openOrders = await program.account.openOrders.fetch(openOrdersPda);
+    assert.equal(openOrders.balances.baseLocked.toNumber(), 10);
+    assert.equal(openOrders.balances.baseFree.toNumber(), 490);
+
+    const tx = await program.methods
+      .cancelOrder(orderId)
+      .accounts({
+        user: user.publicKey,
+        baseTokenMint,
+        quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+        // @ts-ignore
+        orderSlab: orderSlabPda,
+        // @ts-ignore
+        openOrders: openOrdersPda,
+        // @ts-ignore
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+    console.log("Cancel order tx:", tx);
+
+    // The sell's base is back in the free balance and the order is gone
+    openOrders = await program.account.openOrders.fetch(openOrdersPda);
+    assert.equal(openOrders.balances.baseLocked.toNumber(), 0);
+    assert.equal(openOrders.balances.baseFree.toNumber(), 500);
+    assert.isFalse(openOrders.orderIds.some((id) => id.eq(orderId)));
+    const orderSlab = await program.account.orderSlab.fetch(orderSlabPda);
+    assert.equal(orderSlab.askCount, 0);
+  });
+
+  it("Cancel all orders", async () => {
+    for (const price of [20, 25]) {
+      await program.methods
+        .createOrder(1, 0, 0, new anchor.BN(price), new anchor.BN(5))
+        .accounts({
+          user: user.publicKey,
+          baseTokenMint,
+          quoteTokenMint,
+          // @ts-ignore
+          orderBook: orderbookPda,
+          // @ts-ignore
+          orderSlab: orderSlabPda,
+          // @ts-ignore
+          eventQueue: eventQueuePda,
+          // @ts-ignore
+          tradeHistory: tradeHistoryPda,
+          // @ts-ignore
+          candles: candlesPda,
+          // @ts-ignore
+          openOrders: openOrdersPda,
+          referrerOpenOrders: null, // no referrer
+          // @ts-ignore
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+    }
+
+    // Only cancel the sell side so the resting buy from the earlier test stays on the book
+    const tx = await program.methods
+      .cancelAllOrders({ sell: {} })
+      .accounts({
+        user: user.publicKey,
+        baseTokenMint,
+        quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+        // @ts-ignore
+        orderSlab: orderSlabPda,
+        // @ts-ignore
+        openOrders: openOrdersPda,
+        // @ts-ignore
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+    console.log("Cancel all orders tx:", tx);
+
+    // Both sells are refunded and only the buy is left
+    const openOrders = await program.account.openOrders.fetch(openOrdersPda);
+    assert.equal(openOrders.balances.baseLocked.toNumber(), 0);
+    assert.equal(openOrders.balances.baseFree.toNumber(), 500);
+    assert.equal(openOrders.balances.quoteLocked.toNumber(), 1001);
+    assert.equal(openOrders.orderIds.length, 1);
+    const orderSlab = await program.account.orderSlab.fetch(orderSlabPda);
+    assert.equal(orderSlab.askCount, 0);
+    assert.equal(orderSlab.bidCount, 1);
+  });
+
+  it("Create market order", async () => {
+    // The taker sells into the user's resting buy at 10
+    const side = 1; // 0 = buy, 1 = sell
+    const selfTradePrevention = 0; // 0 = cancel taker, 1 = cancel maker, 2 = cancel both, 3 = decrement and cancel
+    const amount = new anchor.BN(5);
+    const worstPrice = new anchor.BN(10);
+    const quoteBudget = new anchor.BN(0); // only used by buys
+
+    const tx = await program.methods
+      .createMarketOrder(side, selfTradePrevention, amount, worstPrice, quoteBudget)
+      .accounts({
+        user: taker.publicKey,
+        baseTokenMint,
+        quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+        // @ts-ignore
+        orderSlab: orderSlabPda,
+        // @ts-ignore
+        eventQueue: eventQueuePda,
+        // @ts-ignore
+        tradeHistory: tradeHistoryPda,
+        // @ts-ignore
+        candles: candlesPda,
+        // @ts-ignore
+        openOrders: takerOpenOrdersPda,
+        referrerOpenOrders: null, // no referrer
+        // @ts-ignore
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([taker])
+      .rpc();
+    console.log("Create market order tx:", tx);
+
+    // 5 filled for 50 quote, less a 5 bps taker fee rounded up to 1
+    const takerOpenOrders = await program.account.openOrders.fetch(takerOpenOrdersPda);
+    assert.equal(takerOpenOrders.balances.baseFree.toNumber(), 95);
+    assert.equal(takerOpenOrders.balances.quoteFree.toNumber(), 49);
+    const orderBook = await program.account.orderbook.fetch(orderbookPda);
+    assert.equal(orderBook.accruedFees.toNumber(), 1);
+
+    // The maker's side waits in the event queue
+    const eventQueue = await program.account.eventQueue.fetch(eventQueuePda);
+    assert.equal(eventQueue.count, 1);
+    const openOrders = await program.account.openOrders.fetch(openOrdersPda);
+    assert.equal(openOrders.balances.baseFree.toNumber(), 500);
+    assert.equal(openOrders.balances.quoteLocked.toNumber(), 1001);
+  });
+
+  it("Consume events", async () => {
+    const limit = 10; // events to settle at most
+
+    // Makers' open orders accounts are passed as remaining accounts
+    const tx = await program.methods
+      .consumeEvents(limit)
+      .accounts({
+        baseTokenMint,
+        quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+        // @ts-ignore
+        eventQueue: eventQueuePda,
+      })
+      .remainingAccounts([{ pubkey: openOrdersPda, isSigner: false, isWritable: true }])
+      .rpc();
+    console.log("Consume events tx:", tx);
+
+    // The maker gets the base and the 50 quote its buy paid leave its lock
+    const eventQueue = await program.account.eventQueue.fetch(eventQueuePda);
+    assert.equal(eventQueue.count, 0);
+    const openOrders = await program.account.openOrders.fetch(openOrdersPda);
+    assert.equal(openOrders.balances.baseFree.toNumber(), 505);
+    assert.equal(openOrders.balances.quoteLocked.toNumber(), 951);
+    assert.equal(openOrders.orderIds.length, 1);
+  });
+
+  it("Audit orderbook", async () => {
+    const tx = await program.methods
+      .audit()
+      .accounts({
+        baseTokenMint,
+        quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+        // @ts-ignore
+        orderSlab: orderSlabPda,
+        // @ts-ignore
+        baseVault: getAssociatedTokenAddressSync(baseTokenMint, orderbookPda, true),
+        // @ts-ignore
+        quoteVault: getAssociatedTokenAddressSync(quoteTokenMint, orderbookPda, true),
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+    console.log("Audit tx:", tx);
+
+    // Nothing was sent to the vaults directly, so they hold exactly the balances and fees
+    const orderBook = await program.account.orderbook.fetch(orderbookPda);
+    const baseVault = await getAccount(provider.connection, getAssociatedTokenAddressSync(baseTokenMint, orderbookPda, true));
+    const quoteVault = await getAccount(provider.connection, getAssociatedTokenAddressSync(quoteTokenMint, orderbookPda, true));
+    const totals = orderBook.totals;
+    assert.equal(Number(baseVault.amount), totals.baseFree.add(totals.baseLocked).toNumber());
+    assert.equal(Number(quoteVault.amount), totals.quoteFree.add(totals.quoteLocked).add(orderBook.accruedFees).toNumber());
+  });
+
+  it("Get market stats", async () => {
+    const stats = await program.methods
+      .getMarketStats()
+      .accounts({
+        baseTokenMint,
+        quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+        // @ts-ignore
+        candles: candlesPda,
+      })
+      .view();
+    console.log("Market stats:", stats);
+
+    // One fill of 5 base at 10, with the rest of the user's buy still resting
+    assert.equal(stats.lastPrice.toNumber(), 10);
+    assert.equal(stats.baseVolume.toNumber(), 5);
+    assert.equal(stats.quoteVolume.toNumber(), 50);
+    assert.equal(stats.tradeCount.toNumber(), 1);
+    assert.equal(stats.baseVolume24h.toNumber(), 5);
+    assert.equal(stats.quoteVolume24h.toNumber(), 50);
+    assert.equal(stats.restingBase.toNumber(), 0);
+    assert.equal(stats.restingQuote.toNumber(), 951);
+  });
+
+  it("Sweep fees", async () => {
+    // Fees are paid out to a quote token account of the authority's choosing
+    const treasury = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      provider.wallet.payer,
+      quoteTokenMint,
+      provider.wallet.publicKey
+    );
+    const quoteVault = getAssociatedTokenAddressSync(quoteTokenMint, orderbookPda, true);
+    const treasuryBefore = await getAccount(provider.connection, treasury.address);
+    const accruedFees = (await program.account.orderbook.fetch(orderbookPda)).accruedFees;
+    assert.equal(accruedFees.toNumber(), 1);
+
+    const tx = await program.methods
+      .sweepFees()
+      .accounts({
+        authority: provider.wallet.publicKey,
+        baseTokenMint,
+        quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+        // @ts-ignore
+        quoteVault,
+        treasury: treasury.address,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+    console.log("Sweep fees tx:", tx);
+
+    const treasuryAfter = await getAccount(provider.connection, treasury.address);
+    assert.equal(Number(treasuryAfter.amount - treasuryBefore.amount), accruedFees.toNumber());
+    const orderBook = await program.account.orderbook.fetch(orderbookPda);
+    assert.equal(orderBook.accruedFees.toNumber(), 0);
+  });
+
   it("Delegate orderbook to ER", async () => {
     const start = Date.now();
     let tx = await program.methods
@@ -269,6 +789,14 @@ describe("clob", () => {
         quoteTokenMint: quoteTokenMint,
         // @ts-ignore
         orderBook: orderbookPda,
+        // @ts-ignore
+        orderSlab: orderSlabPda,
+        // @ts-ignore
+        eventQueue: eventQueuePda,
+        // @ts-ignore
+        tradeHistory: tradeHistoryPda,
+        // @ts-ignore
+        candles: candlesPda,
       })
       .transaction();
     tx.feePayer = provider.wallet.publicKey;
@@ -284,9 +812,38 @@ describe("clob", () => {
     console.log(`${duration}ms (Base Layer) Delegate txHash: ${txHash}`);
   });
   
+  it("Delegate open orders to ER", async () => {
+    const start = Date.now();
+    let tx = await program.methods
+      .delegateOpenOrders()
+      .accounts({
+        user: user.publicKey,
+        baseTokenMint: baseTokenMint,
+        quoteTokenMint: quoteTokenMint,
+        // @ts-ignore
+        orderBook: orderbookPda,
+        // @ts-ignore
+        openOrders: openOrdersPda,
+      })
+      .transaction();
+    tx.feePayer = provider.wallet.publicKey;
+    tx.recentBlockhash = (
+      await provider.connection.getLatestBlockhash()
+    ).blockhash;
+    tx = await providerEphemeralRollup.wallet.signTransaction(tx);
+    const txHash = await provider.sendAndConfirm(tx, [user], {
+      skipPreflight: true,
+      commitment: "confirmed",
+    });
+    const duration = Date.now() - start;
+    console.log(`${duration}ms (Base Layer) Delegate open orders txHash: ${txHash}`);
+  });
+
   it("Create order on ER", async () => {
     const start = Date.now();
     const side = 1; // 0 = buy, 1 = sell
+    const timeInForce = 0; // 0 = GTC, 1 = IOC, 2 = FOK, 3 = post-only, 4 = post-only slide
+    const selfTradePrevention = 0; // 0 = cancel taker, 1 = cancel maker, 2 = cancel both, 3 = decrement and cancel
     const price = new anchor.BN(12);
     const amount = new anchor.BN(50);
   
@@ -300,7 +857,7 @@ describe("clob", () => {
     console.log("quoteTokenMint: ", quoteTokenMint.toString());
     console.log("--------------------------------");
     let tx = await program.methods
-      .createOrder(side, price, amount)
+      .createOrder(side, timeInForce, selfTradePrevention, price, amount)
       .accounts({
         // Fill in required accounts for create_order
         // e.g. user, baseTokenMint, quoteTokenMint, orderbook, systemProgram, etc.
@@ -310,6 +867,17 @@ describe("clob", () => {
         // @ts-ignore
         orderBook: orderbookPda,
         // @ts-ignore
+        orderSlab: orderSlabPda,
+        // @ts-ignore
+        eventQueue: eventQueuePda,
+        // @ts-ignore
+        tradeHistory: tradeHistoryPda,
+        // @ts-ignore
+        candles: candlesPda,
+        // @ts-ignore
+        openOrders: openOrdersPda,
+        referrerOpenOrders: null, // no referrer
+        // @ts-ignore
         systemProgram: anchor.web3.SystemProgram.programId,
       })
       .transaction();
@@ -1,7 +1,7 @@
 'use client'
 
 import * as anchor from '@coral-xyz/anchor'
-import { getClobProgram, getClobProgramId } from '@/lib/clob/clob-exports'
+import { decodeOrderSlab, getClobProgram, getClobProgramId, getOpenOrdersAddress, getOrderSlabAddress } from '@/lib/clob/clob-exports'
 import { useConnection } from '@solana/wallet-adapter-react'
 import { Cluster, PublicKey } from '@solana/web3.js'
 import { useMutation, useQuery } from '@tanstack/react-query'
@@ -34,12 +34,20 @@ export function useClobProgram() {
 
   const initializeOrderbook = useMutation({
     mutationKey: ['clob', 'initialize-orderbook', { cluster }],
-    mutationFn: async ({ baseTokenMint, quoteTokenMint }: { baseTokenMint: PublicKey, quoteTokenMint: PublicKey }) => {
+    mutationFn: async ({
+      baseTokenMint, quoteTokenMint, orderCapacity, tickSize, baseLotSize, minOrderSize
+    }: {
+      baseTokenMint: PublicKey,
+      quoteTokenMint: PublicKey,
+      orderCapacity: number,
+      tickSize: number,
+      baseLotSize: number,
+      minOrderSize: number,
+    }) => {
       try {
-        // Generate a new keypair for the orderbook account
-        
+        // Prices are in quote atoms per base lot and must be a multiple of the tick size
         return program.methods
-          .initializeOrderbook()
+          .initializeOrderbook(orderCapacity, new anchor.BN(tickSize), new anchor.BN(baseLotSize), new anchor.BN(minOrderSize))
           .accounts({
             payer: provider.publicKey,
             baseTokenMint,
@@ -80,8 +88,9 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
   } as SolanaCluster;
   console.log('magicCluster: ', magicCluster);
   const transactionToast = useTransactionToast()
-  const { program, orderBooks } = useClobProgram()
+  const { program, programId, orderBooks } = useClobProgram()
   const provider = useAnchorProvider()
+  const { connection } = useConnection()
   console.log('orderBooks: ', orderBooks);
   const devnetOrderbookQuery = useQuery({
     queryKey: ['clob', 'fetch', { cluster, orderBookAddress }],
@@ -97,6 +106,25 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
   // Use whichever has data, or fallback as needed
   const orderbookQuery = devnetOrderbookQuery.data ? devnetOrderbookQuery : magicOrderbookQuery
 
+  // The resting orders are kept in the order slab rather than in the orderbook account
+  const orderSlabAddress = useMemo(() => getOrderSlabAddress(orderBookAddress, programId), [orderBookAddress, programId])
+  const ordersQuery = useQuery({
+    queryKey: ['clob', 'orders', { cluster, orderBookAddress }],
+    queryFn: async () => {
+      const account = await connection.getAccountInfo(orderSlabAddress)
+      return account ? decodeOrderSlab(account.data) : { bids: [], asks: [] }
+    },
+  })
+
+  // Balances are kept in the user's open orders account, created on their first deposit
+  const openOrdersQuery = useQuery({
+    queryKey: ['clob', 'open-orders', { cluster, orderBookAddress, user: provider.publicKey }],
+    queryFn: () => program.account.openOrders.fetchNullable(getOpenOrdersAddress(orderBookAddress, provider.publicKey, programId)),
+    enabled: !!provider.publicKey,
+  })
+
+  const refetchBook = () => Promise.all([orderbookQuery.refetch(), ordersQuery.refetch(), openOrdersQuery.refetch()])
+
   // Example: create an ER provider (adjust endpoint and wallet as needed)
   const ER_RPC_URL = 'https://devnet.magicblock.app'
   const ER_WS_URL = 'wss://devnet.magicblock.app'
@@ -111,9 +139,13 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
   const createOrderMutation = useMutation({
     mutationKey: ['clob', 'create-order', { cluster, orderBookAddress }],
     mutationFn: async ({
-      side, price, amount, baseTokenMint, quoteTokenMint, isDelegated = true
+      side, price, amount, baseTokenMint, quoteTokenMint, isDelegated = true, timeInForce = 0, selfTradePrevention = 0
     }: {
       side: number,
+      // 0 = good till cancelled, 1 = immediate or cancel, 2 = fill or kill, 3 = post only, 4 = post only slide
+      timeInForce?: number,
+      // 0 = cancel taker, 1 = cancel maker, 2 = cancel both, 3 = decrement and cancel
+      selfTradePrevention?: number,
       price: number,
       amount: number,
       baseTokenMint: PublicKey,
@@ -124,12 +156,12 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
       if (isDelegated) {
         // Use ER provider
         let tx = await program.methods
-          .createOrder(side, new anchor.BN(price), new anchor.BN(amount))
+          .createOrder(side, timeInForce, selfTradePrevention, new anchor.BN(price), new anchor.BN(amount))
           .accounts({
             user: erProvider.wallet.publicKey,
             baseTokenMint,
             quoteTokenMint,
-            // ...other accounts as needed
+            referrerOpenOrders: null,
           })
           .transaction();
 
@@ -144,11 +176,12 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
       } else {
         // Use base layer provider
         return program.methods
-          .createOrder(side, new anchor.BN(price), new anchor.BN(amount))
+          .createOrder(side, timeInForce, selfTradePrevention, new anchor.BN(price), new anchor.BN(amount))
           .accounts({
             user: provider.publicKey,
             baseTokenMint,
             quoteTokenMint,
+            referrerOpenOrders: null,
           })
           .rpc({
             skipPreflight: true,
@@ -158,7 +191,7 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
     onSuccess: (tx) => {
       transactionToast(tx)
       console.log('tx: ', tx);
-      return orderbookQuery.refetch()
+      return refetchBook()
     },
     onError: () => toast.error('Failed to create order'),
   })
@@ -181,7 +214,7 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
     },
     onSuccess: (tx) => {
       transactionToast(tx)
-      return orderbookQuery.refetch()
+      return refetchBook()
     },
     onError: () => toast.error('Failed to delegate orderbook'),
   })
@@ -205,7 +238,7 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
     },
     onSuccess: (tx) => {
       transactionToast(tx)
-      return orderbookQuery.refetch()
+      return refetchBook()
     },
     onError: (error) => {
       console.error('Failed to undelegate orderbook:', error)
@@ -217,19 +250,35 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
     mutationKey: ['clob', 'deposit-balance', { cluster, orderBookAddress }],
     mutationFn: async ({ baseAmount, quoteAmount, baseTokenMint, quoteTokenMint }: 
       { baseAmount: number, quoteAmount: number, baseTokenMint: PublicKey, quoteTokenMint: PublicKey }) => {
+      // Create the user's open orders account along with their first deposit
+      const openOrders = await program.account.openOrders.fetchNullable(
+        getOpenOrdersAddress(orderBookAddress, provider.publicKey, programId)
+      )
+      const preInstructions = openOrders ? [] : [
+        await program.methods
+          .createOpenOrders()
+          .accounts({
+            payer: provider.publicKey,
+            owner: provider.publicKey,
+            baseTokenMint,
+            quoteTokenMint,
+          })
+          .instruction(),
+      ]
       return program.methods
-        .depositBalance(new anchor.BN(baseAmount), new anchor.BN(quoteAmount))
+        .depositBalance(new anchor.BN(quoteAmount), new anchor.BN(baseAmount))
         .accounts({
           user: provider.publicKey,
           baseTokenMint,
           quoteTokenMint,
           tokenProgram: TOKEN_PROGRAM_ID,
         })
+        .preInstructions(preInstructions)
         .rpc()
     },
     onSuccess: (tx) => {
       transactionToast(tx)
-      return orderbookQuery.refetch()
+      return refetchBook()
     },
     onError: () => toast.error('Failed to deposit balance'),
   })
@@ -250,7 +299,7 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
     },
     onSuccess: (tx) => {
       transactionToast(tx)
-      return orderbookQuery.refetch()
+      return refetchBook()
     },
     onError: () => toast.error('Failed to withdraw funds'),
   })
@@ -304,7 +353,7 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
     },
     onSuccess: (tx) => {
       transactionToast(tx)
-      return orderbookQuery.refetch()
+      return refetchBook()
     },
     onError: () => toast.error('Failed to match order'),
   })
@@ -342,7 +391,7 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
     },
     onSuccess: (tx) => {
       transactionToast(tx)
-      return orderbookQuery.refetch()
+      return refetchBook()
     },
     onError: (error) => {
       console.error('Failed to update delegation status:', error)
@@ -352,6 +401,8 @@ export function useClobOrderbook({ orderBookAddress }: { orderBookAddress: Publi
 
   return {
     orderbookQuery,
+    ordersQuery,
+    openOrdersQuery,
     createOrderMutation,
     depositBalanceMutation,
     withdrawFundsMutation,
@@ -4,13 +4,13 @@ import { PublicKey } from '@solana/web3.js'
 import { useMemo, useState } from 'react'
 import { ExplorerLink } from '../cluster/cluster-ui'
 import { useClobProgram, useClobOrderbook } from './clob-data-access'
+import { SlabOrder } from '@/lib/clob/clob-exports'
 import { ellipsify } from '@/lib/utils'
 import { Button } from '@/components/ui/button'
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '../ui/card'
 import { Input } from '@/components/ui/input'
 import { Label } from '@/components/ui/label'
 // import { RadioGroup, RadioGroupItem } from '@/components/ui/radio-group'
-import { useAnchorProvider } from '../solana/solana-provider'
 import { useRouter } from 'next/navigation'
 import { Dialog, DialogContent, DialogHeader, DialogTitle, DialogClose } from '@/components/ui/dialog'
 
@@ -21,11 +21,10 @@ const formatNum = (n: number | string, decimals = 2) => {
   return num.toLocaleString(undefined, { minimumFractionDigits: decimals, maximumFractionDigits: decimals })
 }
 
-// eslint-disable-next-line @typescript-eslint/no-explicit-any
-function OrderbookTable({ orderbook }: { orderbook: any }) {
-  // Extract and sort orders
-  const bids = [...(orderbook?.buys || [])].sort((a, b) => Number(b.price) - Number(a.price))
-  const asks = [...(orderbook?.sells || [])].sort((a, b) => Number(a.price) - Number(b.price))
+function OrderbookTable({ orders }: { orders?: { bids: SlabOrder[], asks: SlabOrder[] } }) {
+  // The order slab keeps each side sorted best price first
+  const bids = orders?.bids || []
+  const asks = orders?.asks || []
   const maxRows = Math.max(bids.length, asks.length)
 
   // Find max amounts for heatmap
@@ -102,6 +101,11 @@ export function ClobInitialize() {
   const { initializeOrderbook } = useClobProgram()
   const [baseTokenMint, setBaseTokenMint] = useState('')
   const [quoteTokenMint, setQuoteTokenMint] = useState('')
+  // Resting orders per side, and the market's price and size granularity
+  const [orderCapacity, setOrderCapacity] = useState('64')
+  const [tickSize, setTickSize] = useState('1')
+  const [baseLotSize, setBaseLotSize] = useState('1')
+  const [minOrderSize, setMinOrderSize] = useState('1')
 
   const handleInitialize = async () => {
     try {
@@ -111,6 +115,10 @@ export function ClobInitialize() {
       await initializeOrderbook.mutateAsync({
         baseTokenMint: baseTokenMintPubkey,
         quoteTokenMint: quoteTokenMintPubkey,
+        orderCapacity: parseInt(orderCapacity),
+        tickSize: parseInt(tickSize),
+        baseLotSize: parseInt(baseLotSize),
+        minOrderSize: parseInt(minOrderSize),
       })
     } catch (error) {
       console.error('Invalid public key format', error)
@@ -144,9 +152,49 @@ export function ClobInitialize() {
                 onChange={(e) => setQuoteTokenMint(e.target.value)}
               />
             </div>
+            <div className="grid gap-2">
+              <Label htmlFor="orderCapacity">Order Capacity</Label>
+              <Input 
+                id="orderCapacity" 
+                type="number"
+                placeholder="Resting orders per side of the book" 
+                value={orderCapacity}
+                onChange={(e) => setOrderCapacity(e.target.value)}
+              />
+            </div>
+            <div className="grid gap-2">
+              <Label htmlFor="tickSize">Tick Size</Label>
+              <Input 
+                id="tickSize" 
+                type="number"
+                placeholder="Price increment, in quote atoms per base lot" 
+                value={tickSize}
+                onChange={(e) => setTickSize(e.target.value)}
+              />
+            </div>
+            <div className="grid gap-2">
+              <Label htmlFor="baseLotSize">Base Lot Size</Label>
+              <Input 
+                id="baseLotSize" 
+                type="number"
+                placeholder="Base atoms per lot" 
+                value={baseLotSize}
+                onChange={(e) => setBaseLotSize(e.target.value)}
+              />
+            </div>
+            <div className="grid gap-2">
+              <Label htmlFor="minOrderSize">Min Order Size</Label>
+              <Input 
+                id="minOrderSize" 
+                type="number"
+                placeholder="Smallest order, in base atoms" 
+                value={minOrderSize}
+                onChange={(e) => setMinOrderSize(e.target.value)}
+              />
+            </div>
             <Button 
               onClick={handleInitialize} 
-              disabled={initializeOrderbook.isPending || !baseTokenMint || !quoteTokenMint}
+              disabled={initializeOrderbook.isPending || !baseTokenMint || !quoteTokenMint || !orderCapacity || !tickSize || !baseLotSize || !minOrderSize}
             >
               Initialize {initializeOrderbook.isPending && '...'}
             </Button>
@@ -209,7 +257,7 @@ export function ClobOrderbookList() {
 }
 
 export function ClobOrderbookDetail({ orderBookAddress }: { orderBookAddress: PublicKey }) {
-  const { orderbookQuery, createOrderMutation, depositBalanceMutation, withdrawFundsMutation, matchOrderMutation, delegateOrderbookMutation, undelegateOrderbookMutation, updateDelegationStatusMutation } = useClobOrderbook({
+  const { orderbookQuery, ordersQuery, createOrderMutation, depositBalanceMutation, withdrawFundsMutation, delegateOrderbookMutation, undelegateOrderbookMutation, updateDelegationStatusMutation } = useClobOrderbook({
     orderBookAddress: new PublicKey(orderBookAddress),
   })
   const [side, setSide] = useState<number>(0) // 0 for buy, 1 for sell
@@ -222,7 +270,6 @@ export function ClobOrderbookDetail({ orderBookAddress }: { orderBookAddress: Pu
   const [showDepositModal, setShowDepositModal] = useState(false)
   const [modalTab, setModalTab] = useState<'deposit' | 'withdraw'>('deposit')
   const [isDelegated, setIsDelegated] = useState<boolean>(false)
-  const provider = useAnchorProvider()
 
   const baseTokenMint = useMemo(() => {
     if (orderbookQuery.data) {
@@ -253,30 +300,9 @@ export function ClobOrderbookDetail({ orderBookAddress }: { orderBookAddress: Pu
       isDelegated
     })
 
+    // create_order matches the order against the book when it is placed, so there is
+    // nothing left to match here
     console.log('createOrderMutation end: ', createOrderMutation);
-    // After order is created, try to find the new order and match it
-    // Wait for orderbookQuery to refetch (it is refetched on createOrderMutation success)
-    setTimeout(() => {
-      const orders = side === 0 ? orderbookQuery.data?.buys : orderbookQuery.data?.sells
-      if (!orders) return
-      // Find the latest order by the current user
-      // eslint-disable-next-line @typescript-eslint/no-explicit-any
-      const myOrders = orders.filter((o: any) => o.owner === provider.publicKey?.toString())
-      if (myOrders.length === 0) return
-      // Assume the latest order is the one just created
-      // eslint-disable-next-line @typescript-eslint/no-explicit-any
-      const latestOrder = myOrders.reduce((a: any, b: any) => (a.timestamp > b.timestamp ? a : b))
-      if (latestOrder && latestOrder.id) {
-        console.log('matchOrderMutation start: ', matchOrderMutation);
-        matchOrderMutation.mutateAsync({
-          orderId: latestOrder.id.toNumber(),
-          baseTokenMint,
-          quoteTokenMint,
-          isDelegated,
-        })
-        console.log('matchOrderMutation end: ', matchOrderMutation);
-      }
-    }, 1000) // Wait 1s for orderbookQuery to update (tweak as needed)
   }
 
   const handleDepositBalance = async () => {
@@ -358,7 +384,7 @@ export function ClobOrderbookDetail({ orderBookAddress }: { orderBookAddress: Pu
       <div className="flex gap-8">
         {/* Left: Orderbook Table */}
         <div className="flex-1">
-          <OrderbookTable orderbook={orderbookQuery.data} />
+          <OrderbookTable orders={ordersQuery.data} />
         </div>
 
         {/* Right: Order Form */}
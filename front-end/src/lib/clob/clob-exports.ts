@@ -1,5 +1,5 @@
 // Here we export some useful types and functions for interacting with the Anchor program.
-import { AnchorProvider, Program } from '@coral-xyz/anchor'
+import { AnchorProvider, BN, Program } from '@coral-xyz/anchor'
 import { Cluster, PublicKey } from '@solana/web3.js'
 import ClobIDL from './target/idl/clob.json'
 import type { Clob } from './target/types/clob'
@@ -25,3 +25,56 @@ export function getClobProgramId(cluster: Cluster) {
       return CLOB_PROGRAM_ID
   }
 }
+
+// Resting orders live in a zero-copy order slab account next to the orderbook, seeded by the orderbook address.
+export function getOrderSlabAddress(orderBook: PublicKey, programId: PublicKey = CLOB_PROGRAM_ID) {
+  return PublicKey.findProgramAddressSync([new TextEncoder().encode('order_slab'), orderBook.toBuffer()], programId)[0]
+}
+
+// Each user's balances and order IDs on an orderbook live in their own open orders account.
+export function getOpenOrdersAddress(orderBook: PublicKey, owner: PublicKey, programId: PublicKey = CLOB_PROGRAM_ID) {
+  return PublicKey.findProgramAddressSync([new TextEncoder().encode('open_orders'), orderBook.toBuffer(), owner.toBuffer()], programId)[0]
+}
+
+export type SlabOrder = {
+  id: BN
+  owner: PublicKey
+  price: BN
+  originalAmount: BN
+  remainingAmount: BN
+  createdAt: BN
+}
+
+// Layout of the order slab account, see clob/programs/clob/src/state/order_slab.rs:
+// [discriminator][header][slot; 2 * capacity][bid index: u32; capacity][ask index: u32; capacity]
+const SLAB_HEADER_OFFSET = 8
+const SLAB_SLOTS_OFFSET = SLAB_HEADER_OFFSET + 48
+const ORDER_SLOT_SIZE = 80
+
+// Decode the orders on both sides of the book, best price first on each side.
+export function decodeOrderSlab(data: Uint8Array): { bids: SlabOrder[]; asks: SlabOrder[] } {
+  const view = new DataView(data.buffer, data.byteOffset, data.byteLength)
+  const capacity = Math.floor((data.length - SLAB_SLOTS_OFFSET) / (2 * ORDER_SLOT_SIZE + 2 * 4))
+  const bidCount = view.getUint32(SLAB_HEADER_OFFSET + 36, true)
+  const askCount = view.getUint32(SLAB_HEADER_OFFSET + 40, true)
+  const bidIndexOffset = SLAB_SLOTS_OFFSET + 2 * capacity * ORDER_SLOT_SIZE
+  const askIndexOffset = bidIndexOffset + capacity * 4
+
+  const u64 = (offset: number) => new BN(data.subarray(offset, offset + 8), 'le')
+  const i64 = (offset: number) => new BN(data.subarray(offset, offset + 8), 'le').fromTwos(64)
+  const order = (slot: number): SlabOrder => {
+    const offset = SLAB_SLOTS_OFFSET + slot * ORDER_SLOT_SIZE
+    return {
+      id: u64(offset),
+      owner: new PublicKey(data.subarray(offset + 8, offset + 40)),
+      price: u64(offset + 40),
+      originalAmount: u64(offset + 48),
+      remainingAmount: u64(offset + 56),
+      createdAt: i64(offset + 64),
+    }
+  }
+  const side = (indexOffset: number, count: number) =>
+    Array.from({ length: count }, (_, i) => order(view.getUint32(indexOffset + i * 4, true)))
+
+  return { bids: side(bidIndexOffset, bidCount), asks: side(askIndexOffset, askCount) }
+}